@@ -0,0 +1,83 @@
+use super::ray::Ray;
+use super::vec::Point3;
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    minimum: Point3,
+    maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            minimum: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            maximum: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.maximum
+    }
+
+    // Parametric range of the ray inside the box clipped to [t_min, t_max]
+    pub fn clip(&self, r: &Ray, mut t_min: f32, mut t_max: f32) -> Option<(f32, f32)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let mut t0 = (self.minimum[a] - r.origin()[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - r.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+
+        Some((t_min, t_max))
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.clip(r, t_min, t_max).is_some()
+    }
+
+    pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+        Aabb::new(
+            Point3::new(
+                box0.min().x().min(box1.min().x()),
+                box0.min().y().min(box1.min().y()),
+                box0.min().z().min(box1.min().z()),
+            ),
+            Point3::new(
+                box0.max().x().max(box1.max().x()),
+                box0.max().y().max(box1.max().y()),
+                box0.max().z().max(box1.max().z()),
+            ),
+        )
+    }
+
+    pub fn overlap(box0: Aabb, box1: Aabb) -> Option<Aabb> {
+        let minimum = Point3::new(
+            box0.min().x().max(box1.min().x()),
+            box0.min().y().max(box1.min().y()),
+            box0.min().z().max(box1.min().z()),
+        );
+        let maximum = Point3::new(
+            box0.max().x().min(box1.max().x()),
+            box0.max().y().min(box1.max().y()),
+            box0.max().z().min(box1.max().z()),
+        );
+
+        if (0..3).all(|a| minimum[a] <= maximum[a]) {
+            Some(Aabb { minimum, maximum })
+        } else {
+            None
+        }
+    }
+}
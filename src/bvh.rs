@@ -0,0 +1,93 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, World};
+use super::ray::Ray;
+
+// Bounding volume hierarchy, for scenes with many small objects
+pub enum Bvh {
+    Leaf(Box<dyn Hit>, Aabb),
+    Node(Box<Bvh>, Box<Bvh>, Aabb),
+}
+
+impl Bvh {
+    // Splits at the median along the longest axis of the centroids.
+    // Every object needs a bounding box.
+    pub fn new(objects: World, time0: f32, time1: f32) -> Bvh {
+        assert!(!objects.is_empty(), "bvh needs at least one object");
+        let boxed = objects
+            .into_iter()
+            .map(|object| {
+                let bounds = object
+                    .bounding_box(time0, time1)
+                    .expect("objects in a bvh need a bounding box");
+                (object, bounds)
+            })
+            .collect();
+        Bvh::build(boxed)
+    }
+
+    fn build(mut objects: Vec<(Box<dyn Hit>, Aabb)>) -> Bvh {
+        if objects.len() == 1 {
+            let (object, bounds) = objects.pop().unwrap();
+            return Bvh::Leaf(object, bounds);
+        }
+
+        let centroid = |b: &Aabb| 0.5 * (b.min() + b.max());
+        let (low, high) = objects.iter().fold(
+            (centroid(&objects[0].1), centroid(&objects[0].1)),
+            |(mut low, mut high), (_, b)| {
+                let c = centroid(b);
+                for a in 0..3 {
+                    low[a] = low[a].min(c[a]);
+                    high[a] = high[a].max(c[a]);
+                }
+                (low, high)
+            },
+        );
+        let extent = high - low;
+        let axis = if extent.x() > extent.y() && extent.x() > extent.z() {
+            0
+        } else if extent.y() > extent.z() {
+            1
+        } else {
+            2
+        };
+
+        objects.sort_by(|a, b| centroid(&a.1)[axis].total_cmp(&centroid(&b.1)[axis]));
+        let right = objects.split_off(objects.len() / 2);
+        let (left, right) = (Bvh::build(objects), Bvh::build(right));
+        let bounds = Aabb::surrounding_box(left.bounds(), right.bounds());
+
+        Bvh::Node(Box::new(left), Box::new(right), bounds)
+    }
+
+    fn bounds(&self) -> Aabb {
+        match self {
+            Bvh::Leaf(_, bounds) | Bvh::Node(_, _, bounds) => *bounds,
+        }
+    }
+}
+
+impl Hit for Bvh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        match self {
+            Bvh::Leaf(object, bounds) => {
+                if !bounds.hit(r, t_min, t_max) {
+                    return None;
+                }
+                object.hit(r, t_min, t_max)
+            }
+            Bvh::Node(left, right, bounds) => {
+                if !bounds.hit(r, t_min, t_max) {
+                    return None;
+                }
+                let hit_left = left.hit(r, t_min, t_max);
+                let t_max = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+                right.hit(r, t_min, t_max).or(hit_left)
+            }
+        }
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bounds())
+    }
+}
@@ -14,6 +14,7 @@ pub struct Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
@@ -50,7 +51,15 @@ impl Camera {
             time1,
         }
     }
-    
+
+    // Mirrors the image left to right, for scenes from left handed renderers
+    pub fn flip_horizontal(mut self) -> Camera {
+        self.lower_left_corner += self.horizontal;
+        self.horizontal = (-1.0) * self.horizontal;
+        self.cu = (-1.0) * self.cu;
+        self
+    }
+
     pub fn get_ray(&self, s: f32, t: f32) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.cu * rd.x() + self.cv * rd.y();
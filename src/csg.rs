@@ -0,0 +1,123 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, Span};
+use super::ray::Ray;
+
+#[derive(Clone, Copy)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Boolean combination of two solids, both have to report their spans
+pub struct Csg {
+    op: CsgOp,
+    a: Box<dyn Hit>,
+    b: Box<dyn Hit>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Box<dyn Hit>, b: Box<dyn Hit>) -> Csg {
+        Csg { op, a, b }
+    }
+
+    pub fn union(a: Box<dyn Hit>, b: Box<dyn Hit>) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: Box<dyn Hit>, b: Box<dyn Hit>) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(a: Box<dyn Hit>, b: Box<dyn Hit>) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+
+    // Skips the spans of an operand when the ray misses its bounds
+    fn operand_spans(operand: &dyn Hit, r: &Ray) -> Vec<Span> {
+        match operand.bounding_box(r.time(), r.time()) {
+            Some(bbox) if !bbox.hit(r, f32::NEG_INFINITY, f32::INFINITY) => Vec::new(),
+            _ => operand.spans(r),
+        }
+    }
+}
+
+impl Hit for Csg {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.spans(r)
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .find(|rec| t_min <= rec.t && rec.t <= t_max)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let box_a = self.a.bounding_box(time0, time1);
+        let box_b = self.b.bounding_box(time0, time1);
+
+        match self.op {
+            CsgOp::Union => Some(Aabb::surrounding_box(box_a?, box_b?)),
+            CsgOp::Intersection => match (box_a, box_b) {
+                (Some(a), Some(b)) => Aabb::overlap(a, b),
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => box_a,
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        // Walk through every boundary of both operands in order, tracking
+        // whether the ray is inside each one and so inside the result.
+        let mut events: Vec<(HitRecord, bool)> = Vec::new();
+        for (spans, from_a) in [
+            (Self::operand_spans(self.a.as_ref(), r), true),
+            (Self::operand_spans(self.b.as_ref(), r), false),
+        ] {
+            for span in spans {
+                events.push((span.enter, from_a));
+                events.push((span.exit, from_a));
+            }
+        }
+        events.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+        let mut in_a = false;
+        let mut in_b = false;
+        let mut enter: Option<HitRecord> = None;
+        let mut result = Vec::new();
+
+        for (mut rec, from_a) in events {
+            let was_inside = self.op.inside(in_a, in_b);
+            if from_a {
+                in_a = !in_a;
+            } else {
+                in_b = !in_b;
+            }
+            let is_inside = self.op.inside(in_a, in_b);
+
+            // The normal already faces the ray, only the side has to be
+            // fixed, which flips for surfaces carved out by a difference.
+            if !was_inside && is_inside {
+                rec.front_face = true;
+                enter = Some(rec);
+            } else if was_inside && !is_inside {
+                rec.front_face = false;
+                if let Some(enter) = enter.take() {
+                    if enter.t < rec.t {
+                        result.push(Span { enter, exit: rec });
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
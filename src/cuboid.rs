@@ -0,0 +1,98 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, Span};
+use super::mat::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::sync::Arc;
+
+pub struct Cuboid {
+    min: Point3,
+    max: Point3,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Cuboid {
+    pub fn new(p0: Point3, p1: Point3, m: Arc<dyn Scatter>) -> Cuboid {
+        Cuboid {
+            min: Point3::new(p0.x().min(p1.x()), p0.y().min(p1.y()), p0.z().min(p1.z())),
+            max: Point3::new(p0.x().max(p1.x()), p0.y().max(p1.y()), p0.z().max(p1.z())),
+            mat: m,
+        }
+    }
+
+    // Slab test, returns the entry and exit distances with the axis crossed at each one.
+    fn slabs(&self, r: &Ray) -> Option<((f32, usize), (f32, usize))> {
+        let mut t_near = (f32::NEG_INFINITY, 0);
+        let mut t_far = (f32::INFINITY, 0);
+
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let mut t0 = (self.min[a] - r.origin()[a]) * inv_d;
+            let mut t1 = (self.max[a] - r.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            if t0 > t_near.0 {
+                t_near = (t0, a);
+            }
+            if t1 < t_far.0 {
+                t_far = (t1, a);
+            }
+            if t_far.0 < t_near.0 {
+                return None;
+            }
+        }
+
+        Some((t_near, t_far))
+    }
+
+    fn record(&self, r: &Ray, root: f32, axis: usize) -> HitRecord {
+        let p = r.at(root);
+        let center = 0.5 * (self.min + self.max);
+        let mut outward_normal = Vec3::new(0.0, 0.0, 0.0);
+        outward_normal[axis] = if p[axis] > center[axis] { 1.0 } else { -1.0 };
+
+        let mut rec = HitRecord::new(r, root, outward_normal, self.mat.clone());
+
+        // Each face is mapped to the whole [0,1]^2 using the other two axes
+        let local = (p - self.min) / (self.max - self.min);
+        rec.u = local[(axis + 1) % 3].clamp(0.0, 1.0);
+        rec.v = local[(axis + 2) % 3].clamp(0.0, 1.0);
+        let size = self.max - self.min;
+        rec.dpdu[(axis + 1) % 3] = size[(axis + 1) % 3];
+        rec.dpdv[(axis + 2) % 3] = size[(axis + 2) % 3];
+
+        rec
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t_near, t_far) = self.slabs(r)?;
+
+        let (root, axis) = if t_min <= t_near.0 && t_near.0 <= t_max {
+            t_near
+        } else if t_min <= t_far.0 && t_far.0 <= t_max {
+            t_far
+        } else {
+            return None;
+        };
+
+        Some(self.record(r, root, axis))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        match self.slabs(r) {
+            Some((t_near, t_far)) if t_near.0 < t_far.0 => vec![Span {
+                enter: self.record(r, t_near.0, t_near.1),
+                exit: self.record(r, t_far.0, t_far.1),
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
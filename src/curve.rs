@@ -0,0 +1,189 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::mat::Scatter;
+use super::onb::Onb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::sync::Arc;
+
+#[derive(Clone, Copy)]
+pub enum CurveShape {
+    // Flat strip always facing the ray, for grass blades
+    Flat,
+    // Shaded as a round tube, for hair and fur
+    Tube,
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    (1.0 - t) * a + t * b
+}
+
+fn bezier(cp: &[Point3; 4], u: f32) -> Point3 {
+    let s = 1.0 - u;
+    s * s * s * cp[0] + 3.0 * s * s * u * cp[1] + 3.0 * s * u * u * cp[2] + u * u * u * cp[3]
+}
+
+fn bezier_derivative(cp: &[Point3; 4], u: f32) -> Vec3 {
+    let s = 1.0 - u;
+    3.0 * s * s * (cp[1] - cp[0]) + 6.0 * s * u * (cp[2] - cp[1]) + 3.0 * u * u * (cp[3] - cp[2])
+}
+
+// De Casteljau split at the middle
+fn split(cp: &[Point3; 4]) -> ([Point3; 4], [Point3; 4]) {
+    let mid = |a: Point3, b: Point3| 0.5 * (a + b);
+    let (p01, p12, p23) = (mid(cp[0], cp[1]), mid(cp[1], cp[2]), mid(cp[2], cp[3]));
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let p0123 = mid(p012, p123);
+    ([cp[0], p01, p012, p0123], [p0123, p123, p23, cp[3]])
+}
+
+// Cubic Bezier strand whose width goes linearly from width0 to width1
+pub struct Curve {
+    cp: [Point3; 4],
+    width: [f32; 2],
+    shape: CurveShape,
+    bounds: Aabb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Curve {
+    pub fn new(cp: [Point3; 4], width0: f32, width1: f32, shape: CurveShape, m: Arc<dyn Scatter>) -> Curve {
+        // The curve stays inside the hull of its control points
+        let half = 0.5 * width0.max(width1);
+        let pad = Vec3::new(half, half, half);
+        let hull = cp[1..]
+            .iter()
+            .fold(Aabb::new(cp[0], cp[0]), |b, &p| Aabb::surrounding_box(b, Aabb::new(p, p)));
+
+        Curve {
+            cp,
+            width: [width0, width1],
+            shape,
+            bounds: Aabb::new(hull.min() - pad, hull.max() + pad),
+            mat: m,
+        }
+    }
+
+    // Control points are in a frame where the ray starts at the origin and runs
+    // along +z, so a segment is hit when it passes close enough to the z axis.
+    // Returns the depth along the ray and the curve parameter.
+    fn recurse(&self, cp: &[Point3; 4], u0: f32, u1: f32, depth: u32, z_min: f32, z_max: f32) -> Option<(f32, f32)> {
+        let w0 = lerp(u0, self.width[0], self.width[1]);
+        let w1 = lerp(u1, self.width[0], self.width[1]);
+        let half = 0.5 * w0.max(w1);
+
+        let mut low = cp[0];
+        let mut high = cp[0];
+        for p in &cp[1..] {
+            for a in 0..3 {
+                low[a] = low[a].min(p[a]);
+                high[a] = high[a].max(p[a]);
+            }
+        }
+        if low.x() - half > 0.0
+            || high.x() + half < 0.0
+            || low.y() - half > 0.0
+            || high.y() + half < 0.0
+            || high.z() + half < z_min
+            || low.z() - half > z_max
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let (a, b) = split(cp);
+            let um = 0.5 * (u0 + u1);
+            let first = self.recurse(&a, u0, um, depth - 1, z_min, z_max);
+            let z_max = first.map_or(z_max, |(z, _)| z);
+            return self.recurse(&b, um, u1, depth - 1, z_min, z_max).or(first);
+        }
+
+        // The axis must be past the start of the segment and before its end
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return None;
+        }
+
+        // Closest point of the chord to the axis
+        let (sx, sy) = (cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y());
+        let denom = sx * sx + sy * sy;
+        if denom == 0.0 {
+            return None;
+        }
+        let w = (-(cp[0].x() * sx + cp[0].y() * sy) / denom).clamp(0.0, 1.0);
+        let u = lerp(w, u0, u1);
+        let width = lerp(u, self.width[0], self.width[1]);
+
+        let pc = bezier(cp, w);
+        if pc.x() * pc.x() + pc.y() * pc.y() > 0.25 * width * width {
+            return None;
+        }
+        if pc.z() < z_min || pc.z() > z_max {
+            return None;
+        }
+
+        Some((pc.z(), u))
+    }
+}
+
+impl Hit for Curve {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.bounds.clip(r, t_min, t_max)?;
+
+        let ray_frame = Onb::build_from_w(r.direction());
+        let length = r.direction().length();
+        let cp = self.cp.map(|p| ray_frame.world_to_local(p - r.origin()));
+
+        // Enough subdivisions for the segments to be flat to a fraction of the width
+        let l0 = (0..2)
+            .map(|i| {
+                let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+                d.x().abs().max(d.y().abs()).max(d.z().abs())
+            })
+            .fold(0.0, f32::max);
+        let eps = 0.05 * self.width[0].max(self.width[1]);
+        let depth = if l0 > 0.0 && eps > 0.0 {
+            ((std::f32::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() * 0.5).ceil().clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let (z, u) = self.recurse(&cp, 0.0, 1.0, depth, t_min * length, t_max * length)?;
+        let t = z / length;
+
+        let dpdu = bezier_derivative(&self.cp, u);
+        let tangent = dpdu.unit_vector();
+        let facing = (-1.0) * r.direction().unit_vector();
+        let mut normal = facing - facing.dot(tangent) * tangent;
+        if normal.near_zero() {
+            normal = Onb::build_from_w(tangent).local(Vec3::new(1.0, 0.0, 0.0));
+        }
+        let normal = normal.unit_vector();
+
+        // Across the strand from -1 on one edge to 1 on the other
+        let side = tangent.cross(normal);
+        let width = lerp(u, self.width[0], self.width[1]);
+        let across = (2.0 * (r.at(t) - bezier(&self.cp, u)).dot(side) / width).clamp(-1.0, 1.0);
+
+        let outward_normal = match self.shape {
+            CurveShape::Flat => normal,
+            CurveShape::Tube => (1.0 - across * across).sqrt() * normal + across * side,
+        };
+
+        let mut rec = HitRecord::new(r, t, outward_normal, self.mat.clone());
+        rec.u = u;
+        rec.v = 0.5 * (across + 1.0);
+        rec.dpdu = dpdu;
+        // v spans the width, which faces the ray
+        rec.dpdv = width * side;
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
@@ -0,0 +1,251 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, Span};
+use super::mat::Scatter;
+use super::onb::Onb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Derivative of a point on a circle around z with respect to its azimuth
+pub fn azimuth_derivative(p: Vec3) -> Vec3 {
+    2.0 * PI * Vec3::new(-p.y(), p.x(), 0.0)
+}
+
+// Unit direction away from the z axis, zero on it
+pub fn radial(p: Vec3) -> Vec3 {
+    let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+    if rho > 0.0 {
+        Vec3::new(p.x(), p.y(), 0.0) / rho
+    } else {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+}
+
+// Angle of p around the local Z axis mapped to [0,1)
+pub fn azimuth(p: Vec3) -> f32 {
+    let phi = p.y().atan2(p.x());
+    if phi < 0.0 {
+        (phi + 2.0 * PI) / (2.0 * PI)
+    } else {
+        phi / (2.0 * PI)
+    }
+}
+
+// Box around a disk of the given radius centered at c and facing along w
+pub fn disk_bounds(c: Point3, w: Vec3, radius: f32) -> Aabb {
+    let extent = Vec3::new(
+        radius * (1.0 - w.x() * w.x()).max(0.0).sqrt(),
+        radius * (1.0 - w.y() * w.y()).max(0.0).sqrt(),
+        radius * (1.0 - w.z() * w.z()).max(0.0).sqrt(),
+    );
+    // Keep flat boxes from collapsing to zero thickness
+    let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+    Aabb::new(c - extent - pad, c + extent + pad)
+}
+
+// Ray in the frame of a shape whose axis starts at base
+fn to_local(r: &Ray, base: Point3, onb: &Onb) -> Ray {
+    Ray::new(onb.world_to_local(r.origin() - base), onb.world_to_local(r.direction()), r.time())
+}
+
+pub struct Cylinder {
+    base: Point3,
+    height: f32,
+    radius: f32,
+    capped: bool,
+    onb: Onb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Cylinder {
+    pub fn new(p0: Point3, p1: Point3, radius: f32, capped: bool, m: Arc<dyn Scatter>) -> Cylinder {
+        Cylinder {
+            base: p0,
+            height: (p1 - p0).length(),
+            radius,
+            capped,
+            onb: Onb::build_from_w(p1 - p0),
+            mat: m,
+        }
+    }
+
+    // Every crossing of the whole line of r with the surface, sorted by distance
+    fn crossings(&self, r: &Ray) -> Vec<HitRecord> {
+        let local = to_local(r, self.base, &self.onb);
+        let (o, d) = (local.origin(), local.direction());
+        let mut crossings = Vec::new();
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        let half_b = o.x() * d.x() + o.y() * d.y();
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if a > 0.0 && discriminant > 0.0 {
+            let sqrtd = discriminant.sqrt();
+            for root in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                let p = local.at(root);
+                if 0.0 <= p.z() && p.z() <= self.height {
+                    let outward_normal = Vec3::new(p.x(), p.y(), 0.0) / self.radius;
+                    let mut rec = HitRecord::new(r, root, self.onb.local(outward_normal), self.mat.clone());
+                    rec.u = azimuth(p);
+                    rec.v = p.z() / self.height;
+                    rec.dpdu = self.onb.local(azimuth_derivative(p));
+                    rec.dpdv = self.onb.local(Vec3::new(0.0, 0.0, self.height));
+                    crossings.push(rec);
+                }
+            }
+        }
+
+        if self.capped && d.z() != 0.0 {
+            for (z, normal_z) in [(0.0, -1.0), (self.height, 1.0)] {
+                let root = (z - o.z()) / d.z();
+                let p = local.at(root);
+                let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                if rho <= self.radius {
+                    let outward_normal = Vec3::new(0.0, 0.0, normal_z);
+                    let mut rec = HitRecord::new(r, root, self.onb.local(outward_normal), self.mat.clone());
+                    rec.u = azimuth(p);
+                    rec.v = rho / self.radius;
+                    rec.dpdu = self.onb.local(azimuth_derivative(p));
+                    rec.dpdv = self.onb.local(self.radius * radial(p));
+                    crossings.push(rec);
+                }
+            }
+        }
+
+        crossings.sort_by(|x, y| x.t.total_cmp(&y.t));
+        crossings
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.crossings(r)
+            .into_iter()
+            .find(|rec| t_min <= rec.t && rec.t <= t_max)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let top = self.base + self.height * self.onb.w();
+        Some(Aabb::surrounding_box(
+            disk_bounds(self.base, self.onb.w(), self.radius),
+            disk_bounds(top, self.onb.w(), self.radius),
+        ))
+    }
+
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        if self.capped {
+            Span::from_crossings(self.crossings(r))
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// Cone standing on its base disk with the apex on top
+pub struct Cone {
+    base: Point3,
+    height: f32,
+    radius: f32,
+    capped: bool,
+    onb: Onb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Cone {
+    pub fn new(base: Point3, apex: Point3, radius: f32, capped: bool, m: Arc<dyn Scatter>) -> Cone {
+        Cone {
+            base,
+            height: (apex - base).length(),
+            radius,
+            capped,
+            onb: Onb::build_from_w(apex - base),
+            mat: m,
+        }
+    }
+
+    // Every crossing of the whole line of r with the surface, sorted by distance
+    fn crossings(&self, r: &Ray) -> Vec<HitRecord> {
+        let local = to_local(r, self.base, &self.onb);
+        let (o, d) = (local.origin(), local.direction());
+        let mut crossings = Vec::new();
+
+        // x^2 + y^2 = k^2 (h - z)^2
+        let k2 = (self.radius / self.height).powi(2);
+        let hz = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let half_b = o.x() * d.x() + o.y() * d.y() + k2 * hz * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * hz * hz;
+
+        let roots = if a.abs() < 1e-8 {
+            if half_b != 0.0 {
+                vec![-c / (2.0 * half_b)]
+            } else {
+                Vec::new()
+            }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant > 0.0 {
+                let sqrtd = discriminant.sqrt();
+                vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+            } else {
+                Vec::new()
+            }
+        };
+
+        for root in roots {
+            let p = local.at(root);
+            if 0.0 <= p.z() && p.z() <= self.height {
+                let outward_normal = Vec3::new(p.x(), p.y(), k2 * (self.height - p.z())).unit_vector();
+                let mut rec = HitRecord::new(r, root, self.onb.local(outward_normal), self.mat.clone());
+                rec.u = azimuth(p);
+                rec.v = p.z() / self.height;
+                rec.dpdu = self.onb.local(azimuth_derivative(p));
+                rec.dpdv = self.onb.local(Vec3::new(0.0, 0.0, self.height) - self.radius * radial(p));
+                crossings.push(rec);
+            }
+        }
+
+        if self.capped && d.z() != 0.0 {
+            let root = -o.z() / d.z();
+            let p = local.at(root);
+            let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+            if rho <= self.radius {
+                let outward_normal = Vec3::new(0.0, 0.0, -1.0);
+                let mut rec = HitRecord::new(r, root, self.onb.local(outward_normal), self.mat.clone());
+                rec.u = azimuth(p);
+                rec.v = rho / self.radius;
+                rec.dpdu = self.onb.local(azimuth_derivative(p));
+                rec.dpdv = self.onb.local(self.radius * radial(p));
+                crossings.push(rec);
+            }
+        }
+
+        crossings.sort_by(|x, y| x.t.total_cmp(&y.t));
+        crossings
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.crossings(r)
+            .into_iter()
+            .find(|rec| t_min <= rec.t && rec.t <= t_max)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let apex = self.base + self.height * self.onb.w();
+        Some(Aabb::surrounding_box(
+            disk_bounds(self.base, self.onb.w(), self.radius),
+            Aabb::new(apex, apex),
+        ))
+    }
+
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        if self.capped {
+            Span::from_crossings(self.crossings(r))
+        } else {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use super::aabb::Aabb;
+use super::cylinder::{azimuth, azimuth_derivative, disk_bounds, radial};
+use super::hit::{Hit, HitRecord};
+use super::mat::Scatter;
+use super::onb::Onb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::sync::Arc;
+
+// Crossing of r with the plane through center facing along the onb w axis,
+// returns the root and the hit point in local coordinates.
+fn plane_hit(r: &Ray, center: Point3, onb: &Onb, t_min: f32, t_max: f32) -> Option<(f32, Vec3)> {
+    let o = onb.world_to_local(r.origin() - center);
+    let d = onb.world_to_local(r.direction());
+    if d.z() == 0.0 {
+        return None;
+    }
+
+    let root = -o.z() / d.z();
+    if root < t_min || t_max < root {
+        return None;
+    }
+
+    Some((root, o + root * d))
+}
+
+pub struct Disk {
+    center: Point3,
+    radius: f32,
+    onb: Onb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f32, m: Arc<dyn Scatter>) -> Disk {
+        Disk {
+            center,
+            radius,
+            onb: Onb::build_from_w(normal),
+            mat: m,
+        }
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (root, p) = plane_hit(r, self.center, &self.onb, t_min, t_max)?;
+        let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+        if rho > self.radius {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(r, root, self.onb.w(), self.mat.clone());
+        rec.u = azimuth(p);
+        rec.v = rho / self.radius;
+        rec.dpdu = self.onb.local(azimuth_derivative(p));
+        rec.dpdv = self.onb.local(self.radius * radial(p));
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(disk_bounds(self.center, self.onb.w(), self.radius))
+    }
+}
+
+// Flat ring between two radii
+pub struct Annulus {
+    center: Point3,
+    inner_radius: f32,
+    outer_radius: f32,
+    onb: Onb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Annulus {
+    pub fn new(center: Point3, normal: Vec3, inner_radius: f32, outer_radius: f32, m: Arc<dyn Scatter>) -> Annulus {
+        Annulus {
+            center,
+            inner_radius,
+            outer_radius,
+            onb: Onb::build_from_w(normal),
+            mat: m,
+        }
+    }
+}
+
+impl Hit for Annulus {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (root, p) = plane_hit(r, self.center, &self.onb, t_min, t_max)?;
+        let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+        if rho < self.inner_radius || rho > self.outer_radius {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(r, root, self.onb.w(), self.mat.clone());
+        rec.u = azimuth(p);
+        rec.v = (rho - self.inner_radius) / (self.outer_radius - self.inner_radius);
+        rec.dpdu = self.onb.local(azimuth_derivative(p));
+        rec.dpdv = self.onb.local((self.outer_radius - self.inner_radius) * radial(p));
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(disk_bounds(self.center, self.onb.w(), self.outer_radius))
+    }
+}
@@ -0,0 +1,240 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::mat::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::sync::Arc;
+
+// Result of a distance estimator, iterations are normalized to [0,1]
+struct Estimate {
+    distance: f32,
+    iterations: f32,
+    trap: f32,
+}
+
+trait Estimator {
+    // Point in the local frame of the fractal, where it fits in a sphere of radius bound()
+    fn estimate(&self, p: Vec3) -> Estimate;
+    fn bound(&self) -> f32;
+}
+
+const MAX_STEPS: u32 = 256;
+const EPSILON: f32 = 2e-4;
+
+// Sphere traces the estimator placed at center and scaled by scale. The hit
+// record gets the normalized iteration count in u and the orbit trap in v,
+// so textures can color the fractal with them.
+fn march<E: Estimator>(
+    fractal: &E,
+    center: Point3,
+    scale: f32,
+    mat: &Arc<dyn Scatter>,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord> {
+    let (mut t, t_end) = bounds(center, scale, fractal.bound()).clip(r, t_min, t_max)?;
+    let ray_length = r.direction().length();
+    let local = |p: Point3| (p - center) / scale;
+
+    // Rays leaving the surface start on it and have to get away before a hit counts
+    let mut leaving = fractal.estimate(local(r.at(t))).distance < EPSILON;
+    for _ in 0..MAX_STEPS {
+        let p = r.at(t);
+        let estimate = fractal.estimate(local(p));
+        let d = estimate.distance * scale;
+        if leaving {
+            leaving = d < EPSILON * scale;
+        } else if d < EPSILON * scale {
+            // Gradient of the estimator by central differences on a tetrahedron
+            let h = EPSILON;
+            let k = [
+                Vec3::new(1.0, -1.0, -1.0),
+                Vec3::new(-1.0, -1.0, 1.0),
+                Vec3::new(-1.0, 1.0, -1.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            ];
+            let outward_normal = k
+                .iter()
+                .fold(Vec3::new(0.0, 0.0, 0.0), |n, &k| {
+                    n + fractal.estimate(local(p) + h * k).distance * k
+                })
+                .unit_vector();
+
+            let mut rec = HitRecord::new(r, t, outward_normal, mat.clone());
+            rec.u = estimate.iterations;
+            rec.v = estimate.trap;
+            return Some(rec);
+        }
+
+        t += d.max(EPSILON * scale) / ray_length;
+        if t > t_end {
+            return None;
+        }
+    }
+
+    None
+}
+
+fn bounds(center: Point3, scale: f32, bound: f32) -> Aabb {
+    let extent = Vec3::new(scale * bound, scale * bound, scale * bound);
+    Aabb::new(center - extent, center + extent)
+}
+
+pub struct Mandelbulb {
+    center: Point3,
+    scale: f32,
+    power: f32,
+    iterations: u32,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Mandelbulb {
+    pub fn new(center: Point3, scale: f32, power: f32, iterations: u32, m: Arc<dyn Scatter>) -> Mandelbulb {
+        Mandelbulb {
+            center,
+            scale,
+            power,
+            iterations,
+            mat: m,
+        }
+    }
+}
+
+impl Estimator for Mandelbulb {
+    fn estimate(&self, p: Vec3) -> Estimate {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut trap = f32::INFINITY;
+        let mut i = 0;
+
+        while i < self.iterations {
+            r = z.length();
+            if r > 2.0 {
+                break;
+            }
+            trap = trap.min(r);
+
+            // Spherical coordinates raised to the power
+            let theta = (z.z() / r).clamp(-1.0, 1.0).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z = zr * Vec3::new(theta.sin() * phi.cos(), phi.sin() * theta.sin(), theta.cos()) + p;
+            i += 1;
+        }
+
+        Estimate {
+            distance: if r > 0.0 { 0.5 * r.ln() * r / dr } else { 0.0 },
+            iterations: i as f32 / self.iterations as f32,
+            trap: trap.min(1.0),
+        }
+    }
+
+    fn bound(&self) -> f32 {
+        1.25
+    }
+}
+
+impl Hit for Mandelbulb {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        march(self, self.center, self.scale, &self.mat, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(bounds(self.center, self.scale, self.bound()))
+    }
+}
+
+type Quaternion = [f32; 4];
+
+fn quaternion_mul(a: Quaternion, b: Quaternion) -> Quaternion {
+    [
+        a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+        a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+        a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+        a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0],
+    ]
+}
+
+fn quaternion_length(q: Quaternion) -> f32 {
+    q.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+// Julia set of q^2 + c in quaternion space, sliced at w = slice
+pub struct QuaternionJulia {
+    center: Point3,
+    scale: f32,
+    c: Quaternion,
+    slice: f32,
+    iterations: u32,
+    mat: Arc<dyn Scatter>,
+}
+
+impl QuaternionJulia {
+    pub fn new(
+        center: Point3,
+        scale: f32,
+        c: Quaternion,
+        slice: f32,
+        iterations: u32,
+        m: Arc<dyn Scatter>,
+    ) -> QuaternionJulia {
+        QuaternionJulia {
+            center,
+            scale,
+            c,
+            slice,
+            iterations,
+            mat: m,
+        }
+    }
+}
+
+impl Estimator for QuaternionJulia {
+    fn estimate(&self, p: Vec3) -> Estimate {
+        let mut q = [p.x(), p.y(), p.z(), self.slice];
+        let mut dq = [1.0, 0.0, 0.0, 0.0];
+        let mut trap = f32::INFINITY;
+        let mut i = 0;
+
+        while i < self.iterations {
+            let r = quaternion_length(q);
+            if r > 4.0 {
+                break;
+            }
+            trap = trap.min(r);
+
+            // dq' = 2 q dq, q' = q^2 + c
+            dq = quaternion_mul(q, dq).map(|x| 2.0 * x);
+            q = quaternion_mul(q, q);
+            for (x, c) in q.iter_mut().zip(self.c) {
+                *x += c;
+            }
+            i += 1;
+        }
+
+        let r = quaternion_length(q);
+        let dr = quaternion_length(dq);
+        Estimate {
+            distance: if r > 0.0 && dr > 0.0 { 0.5 * r * r.ln() / dr } else { 0.0 },
+            iterations: i as f32 / self.iterations as f32,
+            trap: trap.min(1.0),
+        }
+    }
+
+    fn bound(&self) -> f32 {
+        1.6
+    }
+}
+
+impl Hit for QuaternionJulia {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        march(self, self.center, self.scale, &self.mat, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(bounds(self.center, self.scale, self.bound()))
+    }
+}
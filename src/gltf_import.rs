@@ -0,0 +1,256 @@
+use super::camera::Camera;
+use super::hit::World;
+use super::image::Image;
+use super::mat::{MetallicRoughness, NormalMap, Scatter};
+use super::mesh::{Mesh, TriangleMesh};
+use super::texture::{ImageTexture, SolidColor, Texture};
+use super::transform::Transform;
+use super::vec::{Color, Point3, Vec3};
+use gltf::image::Format;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Decoded glTF image, color textures are stored in sRGB
+fn to_image(data: &gltf::image::Data, srgb: bool) -> io::Result<Image> {
+    let (channels, bytes) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let sample = |b: &[u8]| match bytes {
+        1 => b[0] as f32 / 255.0,
+        2 => u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0,
+        _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+    };
+    let decode = |c: f32| if srgb { srgb_to_linear(c) } else { c };
+
+    let pixels = data
+        .pixels
+        .chunks_exact(channels * bytes)
+        .map(|p| {
+            let c: Vec<f32> = p.chunks_exact(bytes).map(sample).collect();
+            if channels < 3 {
+                Color::new(decode(c[0]), decode(c[0]), decode(c[0]))
+            } else {
+                Color::new(decode(c[0]), decode(c[1]), decode(c[2]))
+            }
+        })
+        .collect::<Vec<Color>>();
+
+    let (width, height) = (data.width as usize, data.height as usize);
+    if pixels.len() != width * height {
+        return Err(invalid_data("gltf image has the wrong size".to_string()));
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+struct Loader {
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+    // Keyed by image index and whether it is sRGB
+    textures: HashMap<(usize, bool), Arc<dyn Texture>>,
+    materials: HashMap<Option<usize>, Arc<dyn Scatter>>,
+    meshes: World,
+    camera: Option<(Transform, f32)>,
+}
+
+impl Loader {
+    fn texture(&mut self, texture: Option<gltf::Texture>, srgb: bool) -> io::Result<Option<Arc<dyn Texture>>> {
+        let index = match texture {
+            Some(texture) => texture.source().index(),
+            None => return Ok(None),
+        };
+        if let Some(texture) = self.textures.get(&(index, srgb)) {
+            return Ok(Some(texture.clone()));
+        }
+
+        let data = self
+            .images
+            .get(index)
+            .ok_or_else(|| invalid_data(format!("gltf image {} is missing", index)))?;
+        let texture: Arc<dyn Texture> = Arc::new(ImageTexture::new(to_image(data, srgb)?));
+        self.textures.insert((index, srgb), texture.clone());
+        Ok(Some(texture))
+    }
+
+    // Texture times factor, or just the factor
+    fn scaled_texture(&mut self, info: Option<gltf::texture::Info>, factor: Color, srgb: bool) -> io::Result<Arc<dyn Texture>> {
+        Ok(match self.texture(info.map(|info| info.texture()), srgb)? {
+            Some(texture) => Arc::new(Scaled { texture, factor }),
+            None => Arc::new(SolidColor::new(factor)),
+        })
+    }
+
+    fn material(&mut self, material: gltf::Material) -> io::Result<Arc<dyn Scatter>> {
+        if let Some(mat) = self.materials.get(&material.index()) {
+            return Ok(mat.clone());
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color = self.scaled_texture(pbr.base_color_texture(), Color::new(r, g, b), true)?;
+
+        let [r, g, b] = material.emissive_factor();
+        let strength = material.emissive_strength().unwrap_or(1.0);
+        let emission = self.scaled_texture(material.emissive_texture(), strength * Color::new(r, g, b), true)?;
+
+        let mut mat = MetallicRoughness::new(base_color, pbr.metallic_factor(), pbr.roughness_factor()).with_emission(emission);
+        if let Some(texture) = self.texture(pbr.metallic_roughness_texture().map(|info| info.texture()), false)? {
+            mat = mat.with_metallic_roughness_texture(texture);
+        }
+
+        let mut mat: Arc<dyn Scatter> = Arc::new(mat);
+        if let Some(normal) = material.normal_texture() {
+            if let Some(texture) = self.texture(Some(normal.texture()), false)? {
+                mat = Arc::new(NormalMap::new(texture, mat).with_scale(normal.scale()));
+            }
+        }
+        self.materials.insert(material.index(), mat.clone());
+        Ok(mat)
+    }
+
+    fn mesh(&mut self, mesh: gltf::Mesh, transform: &Transform) -> io::Result<()> {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let buffers = &self.buffers;
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+            let positions: Vec<Point3> = match reader.read_positions() {
+                Some(positions) => positions.map(|[x, y, z]| transform.point(Point3::new(x, y, z))).collect(),
+                None => continue,
+            };
+            let normals = reader
+                .read_normals()
+                .map(|normals| normals.map(|[x, y, z]| transform.normal(Vec3::new(x, y, z))).collect())
+                .unwrap_or_default();
+            // glTF puts v = 0 at the top of the image
+            let uvs = reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().map(|[u, v]| (u, 1.0 - v)).collect())
+                .unwrap_or_default();
+            let colors = reader
+                .read_colors(0)
+                .map(|colors| colors.into_rgb_f32().map(|[r, g, b]| Color::new(r, g, b)).collect())
+                .unwrap_or_default();
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+
+            let triangles: Vec<[usize; 3]> = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+            if triangles.is_empty() {
+                continue;
+            }
+            if indices.iter().any(|&i| i >= positions.len()) {
+                return Err(invalid_data("gltf primitive refers to a missing vertex".to_string()));
+            }
+
+            let mat = self.material(primitive.material())?;
+            let mesh = Mesh {
+                positions,
+                normals,
+                colors,
+                uvs,
+                triangles,
+            };
+            self.meshes.push(Box::new(TriangleMesh::new(mesh, mat)));
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: gltf::Node, parent: &Transform) -> io::Result<()> {
+        let transform = parent.compose(&Transform::from_columns(node.transform().matrix()));
+
+        if let Some(mesh) = node.mesh() {
+            self.mesh(mesh, &transform)?;
+        }
+        if let Some(camera) = node.camera() {
+            if let (gltf::camera::Projection::Perspective(perspective), None) = (camera.projection(), &self.camera) {
+                self.camera = Some((transform, perspective.yfov().to_degrees()));
+            }
+        }
+        for child in node.children() {
+            self.node(child, &transform)?;
+        }
+        Ok(())
+    }
+}
+
+// Texture value multiplied by a constant factor
+struct Scaled {
+    texture: Arc<dyn Texture>,
+    factor: Color,
+}
+
+impl Texture for Scaled {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color {
+        self.factor * self.texture.value(u, v, p)
+    }
+}
+
+// Meshes of the default scene of a .gltf or .glb file with their materials
+// mapped to MetallicRoughness, normal textures applied with NormalMap, and the
+// first perspective camera if there is one. Alpha and texture coordinate sets
+// other than the first are ignored.
+pub fn load<P: AsRef<Path>>(path: P, aspect_ratio: f32) -> io::Result<(World, Option<Camera>)> {
+    let (document, buffers, images) =
+        gltf::import(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let mut loader = Loader {
+        buffers,
+        images,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        meshes: World::new(),
+        camera: None,
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| invalid_data("gltf file has no scene".to_string()))?;
+    for node in scene.nodes() {
+        loader.node(node, &Transform::identity())?;
+    }
+
+    // Cameras look down their local -z with y up
+    let camera = loader.camera.map(|(transform, vfov)| {
+        Camera::new(
+            transform.point(Point3::new(0.0, 0.0, 0.0)),
+            transform.point(Point3::new(0.0, 0.0, -1.0)),
+            transform.vector(Vec3::new(0.0, 1.0, 0.0)),
+            vfov,
+            aspect_ratio,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        )
+    });
+
+    Ok((loader.meshes, camera))
+}
@@ -0,0 +1,281 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::image::Image;
+use super::mat::Scatter;
+use super::mesh::hit_triangle;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Clone, Copy)]
+pub enum CellShape {
+    // Two flat triangles per cell
+    Triangles,
+    // Bilinear patch through the four corners
+    Bilinear,
+}
+
+// Grid of heights over the XZ plane
+pub struct Heightfield {
+    nx: usize,
+    nz: usize,
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+    corner: Point3,
+    cell_size: Vec3,
+    shape: CellShape,
+    bounds: Aabb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Heightfield {
+    // heights holds nx * nz samples in [0,1], x varies fastest. The field covers
+    // size.x by size.z from corner and heights are scaled by size.y.
+    pub fn new(
+        nx: usize,
+        nz: usize,
+        heights: Vec<f32>,
+        corner: Point3,
+        size: Vec3,
+        shape: CellShape,
+        m: Arc<dyn Scatter>,
+    ) -> Heightfield {
+        assert!(nx >= 2 && nz >= 2, "heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), nx * nz, "height count does not match grid size");
+
+        let cell_size = Vec3::new(size.x() / (nx - 1) as f32, size.y(), size.z() / (nz - 1) as f32);
+        let heights: Vec<f32> = heights.iter().map(|h| corner.y() + h * size.y()).collect();
+
+        // Smooth vertex normals from central differences
+        let at = |i: usize, j: usize| heights[j * nx + i];
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let dhdx = (at(i1, j) - at(i0, j)) / ((i1 - i0) as f32 * cell_size.x());
+                let dhdz = (at(i, j1) - at(i, j0)) / ((j1 - j0) as f32 * cell_size.z());
+                normals.push(Vec3::new(-dhdx, 1.0, -dhdz).unit_vector());
+            }
+        }
+
+        let (low, high) = heights
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+        let bounds = Aabb::new(
+            Point3::new(corner.x(), low - 0.0001, corner.z()),
+            Point3::new(corner.x() + size.x(), high + 0.0001, corner.z() + size.z()),
+        );
+
+        Heightfield {
+            nx,
+            nz,
+            heights,
+            normals,
+            corner,
+            cell_size,
+            shape,
+            bounds,
+            mat: m,
+        }
+    }
+
+    // Heights from the brightness of a Netpbm image, its top row is at the lowest Z
+    pub fn from_image<P: AsRef<Path>>(
+        path: P,
+        corner: Point3,
+        size: Vec3,
+        shape: CellShape,
+        m: Arc<dyn Scatter>,
+    ) -> io::Result<Heightfield> {
+        let image = Image::load(path)?;
+        if image.width() < 2 || image.height() < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("height map must be at least 2x2, found {}x{}", image.width(), image.height()),
+            ));
+        }
+        let mut heights = Vec::with_capacity(image.width() * image.height());
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                let c = image.pixel(x, y);
+                heights.push(0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b());
+            }
+        }
+
+        Ok(Heightfield::new(image.width(), image.height(), heights, corner, size, shape, m))
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        Point3::new(
+            self.corner.x() + i as f32 * self.cell_size.x(),
+            self.heights[j * self.nx + i],
+            self.corner.z() + j as f32 * self.cell_size.z(),
+        )
+    }
+
+    fn normal(&self, i: usize, j: usize) -> Vec3 {
+        self.normals[j * self.nx + i]
+    }
+
+    // Intersection with cell (i, j) inside [t0, t1], returns the root, the
+    // smooth normal and the normal of the cell surface itself
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, t0: f32, t1: f32) -> Option<(f32, Vec3, Vec3)> {
+        let p00 = self.vertex(i, j);
+        let p10 = self.vertex(i + 1, j);
+        let p01 = self.vertex(i, j + 1);
+        let p11 = self.vertex(i + 1, j + 1);
+
+        // Skip cells the ray passes over or under
+        let (y0, y1) = (r.at(t0).y(), r.at(t1).y());
+        let cell_low = p00.y().min(p10.y()).min(p01.y()).min(p11.y());
+        let cell_high = p00.y().max(p10.y()).max(p01.y()).max(p11.y());
+        if y0.min(y1) > cell_high || y0.max(y1) < cell_low {
+            return None;
+        }
+
+        match self.shape {
+            CellShape::Triangles => {
+                let n = [self.normal(i, j), self.normal(i + 1, j), self.normal(i, j + 1), self.normal(i + 1, j + 1)];
+                [(p00, p10, p11, n[0], n[1], n[3]), (p00, p11, p01, n[0], n[3], n[2])]
+                    .iter()
+                    .filter_map(|&(a, b, c, na, nb, nc)| {
+                        let (t, u, v) = hit_triangle(r, a, b, c)?;
+                        if t < t0 || t > t1 {
+                            return None;
+                        }
+                        let smooth = ((1.0 - u - v) * na + u * nb + v * nc).unit_vector();
+                        Some((t, smooth, (b - a).cross(c - a).unit_vector()))
+                    })
+                    .min_by(|x, y| x.0.total_cmp(&y.0))
+            }
+            CellShape::Bilinear => {
+                // Local coordinates inside the cell are linear in t, so
+                // ray_y(t) - h(fx(t), fz(t)) is a quadratic in t.
+                let o = r.origin();
+                let d = r.direction();
+                let fx0 = (o.x() - p00.x()) / self.cell_size.x();
+                let fxd = d.x() / self.cell_size.x();
+                let fz0 = (o.z() - p00.z()) / self.cell_size.z();
+                let fzd = d.z() / self.cell_size.z();
+
+                // h = h00 + a fx + b fz + c fx fz
+                let h00 = p00.y();
+                let a = p10.y() - h00;
+                let b = p01.y() - h00;
+                let c = p11.y() - p10.y() - p01.y() + h00;
+
+                let qa = -c * fxd * fzd;
+                let qb = d.y() - a * fxd - b * fzd - c * (fx0 * fzd + fz0 * fxd);
+                let qc = o.y() - h00 - a * fx0 - b * fz0 - c * fx0 * fz0;
+
+                let mut roots = Vec::with_capacity(2);
+                if qa.abs() < 1e-9 {
+                    if qb != 0.0 {
+                        roots.push(-qc / qb);
+                    }
+                } else {
+                    let discriminant = qb * qb - 4.0 * qa * qc;
+                    if discriminant >= 0.0 {
+                        let sqrtd = discriminant.sqrt();
+                        roots.push((-qb - sqrtd) / (2.0 * qa));
+                        roots.push((-qb + sqrtd) / (2.0 * qa));
+                    }
+                }
+
+                let t = roots
+                    .into_iter()
+                    .filter(|&t| t0 <= t && t <= t1)
+                    .min_by(|x, y| x.total_cmp(y))?;
+                let fx = (fx0 + t * fxd).clamp(0.0, 1.0);
+                let fz = (fz0 + t * fzd).clamp(0.0, 1.0);
+                let normal = (1.0 - fx) * (1.0 - fz) * self.normal(i, j)
+                    + fx * (1.0 - fz) * self.normal(i + 1, j)
+                    + (1.0 - fx) * fz * self.normal(i, j + 1)
+                    + fx * fz * self.normal(i + 1, j + 1);
+
+                // Of the bilinear patch, from the slopes of h along x and z
+                let dhdx = (a + c * fz) / self.cell_size.x();
+                let dhdz = (b + c * fx) / self.cell_size.z();
+                let surface = Vec3::new(-dhdx, 1.0, -dhdz).unit_vector();
+
+                Some((t, normal.unit_vector(), surface))
+            }
+        }
+    }
+}
+
+impl Hit for Heightfield {
+    // Walks the cells under the ray in order with a 2D DDA
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t_start, t_end) = self.bounds.clip(r, t_min, t_max)?;
+
+        let cells = [self.nx - 1, self.nz - 1];
+        let dir = [r.direction().x() / self.cell_size.x(), r.direction().z() / self.cell_size.z()];
+        let start = r.at(t_start);
+        let grid_start = [
+            (start.x() - self.corner.x()) / self.cell_size.x(),
+            (start.z() - self.corner.z()) / self.cell_size.z(),
+        ];
+
+        let mut cell = [0usize; 2];
+        let mut step = [0isize; 2];
+        let mut t_next = [f32::INFINITY; 2];
+        let mut t_delta = [f32::INFINITY; 2];
+        for a in 0..2 {
+            cell[a] = (grid_start[a].floor().max(0.0) as usize).min(cells[a] - 1);
+            if dir[a] > 0.0 {
+                step[a] = 1;
+                t_delta[a] = 1.0 / dir[a];
+                t_next[a] = t_start + ((cell[a] + 1) as f32 - grid_start[a]) / dir[a];
+            } else if dir[a] < 0.0 {
+                step[a] = -1;
+                t_delta[a] = -1.0 / dir[a];
+                t_next[a] = t_start + (cell[a] as f32 - grid_start[a]) / dir[a];
+            }
+        }
+
+        let mut t_cell = t_start;
+        loop {
+            let t_exit = t_next[0].min(t_next[1]).min(t_end);
+            // Small overlap so hits right on a cell edge are not lost
+            let slack = 1e-4 * (t_exit - t_cell).abs().max(1e-3);
+            if let Some((t, shading_normal, surface_normal)) = self.hit_cell(r, cell[0], cell[1], t_cell - slack, t_exit + slack) {
+                if t_min <= t && t <= t_max {
+                    let mut rec = HitRecord::new(r, t, shading_normal, self.mat.clone());
+                    rec.set_geometric_normal(surface_normal);
+                    let p = rec.p - self.corner;
+                    rec.u = (p.x() / (cells[0] as f32 * self.cell_size.x())).clamp(0.0, 1.0);
+                    rec.v = (p.z() / (cells[1] as f32 * self.cell_size.z())).clamp(0.0, 1.0);
+
+                    // Along x and z over the surface, following the slope of the normal
+                    let n = shading_normal;
+                    if n.y().abs() > 1e-6 {
+                        rec.dpdu = cells[0] as f32 * self.cell_size.x() * Vec3::new(1.0, -n.x() / n.y(), 0.0);
+                        rec.dpdv = cells[1] as f32 * self.cell_size.z() * Vec3::new(0.0, -n.z() / n.y(), 1.0);
+                    }
+                    return Some(rec);
+                }
+            }
+
+            if t_exit >= t_end {
+                return None;
+            }
+
+            let a = if t_next[0] < t_next[1] { 0 } else { 1 };
+            let next = cell[a] as isize + step[a];
+            if next < 0 || next >= cells[a] as isize {
+                return None;
+            }
+            cell[a] = next as usize;
+            t_cell = t_next[a];
+            t_next[a] += t_delta[a];
+        }
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
@@ -1,14 +1,48 @@
+use crate::aabb::Aabb;
 use crate::mat::Scatter;
 use crate::{ray::Ray, vec::*};
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub p: Point3,
+    // Shading normal, facing the ray. Smooth mesh and heightfield normals bend
+    // it away from the geometric normal of the actual surface, and ray_color
+    // drops the paths that cross one but not the other. Normal and bump maps
+    // bend a copy of it and check their own paths against that copy instead.
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
     pub mat: std::sync::Arc<dyn Scatter>,
     pub t: f32,
+    pub u: f32,
+    pub v: f32,
+    // Surface derivatives along u and v, the tangent frame of anisotropic
+    // materials. Zero on implicit surfaces that have no parameterization.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // Interpolated vertex color of meshes that have them
+    pub vertex_color: Option<Color>,
     pub front_face: bool,
 }
 
+// Stretch of a ray inside a solid, from where it enters to where it leaves
+#[derive(Clone)]
+pub struct Span {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
+impl Span {
+    // Pairs up the sorted crossings of the whole line of a ray with a closed surface
+    pub fn from_crossings(crossings: Vec<HitRecord>) -> Vec<Span> {
+        let mut crossings = crossings.into_iter();
+        let mut spans = Vec::new();
+        while let (Some(enter), Some(exit)) = (crossings.next(), crossings.next()) {
+            spans.push(Span { enter, exit });
+        }
+        spans
+    }
+}
+
 pub type World = Vec<Box<dyn Hit>>;
 
 impl Hit for World {
@@ -26,9 +60,40 @@ impl Hit for World {
 
         tmp_rec
     }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let mut output_box: Option<Aabb> = None;
+
+        for object in self {
+            let temp_box = object.bounding_box(time0, time1)?;
+            output_box = Some(match output_box {
+                Some(b) => Aabb::surrounding_box(b, temp_box),
+                None => temp_box,
+            });
+        }
+
+        output_box
+    }
 }
 impl HitRecord {
-    
+    pub fn new(r: &Ray, t: f32, outward_normal: Vec3, mat: std::sync::Arc<dyn Scatter>) -> HitRecord {
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal: outward_normal,
+            geometric_normal: outward_normal,
+            mat,
+            t,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::new(0.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, 0.0),
+            vertex_color: None,
+            front_face: false,
+        };
+        rec.set_face_normal(r, outward_normal);
+        rec
+    }
+
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -36,9 +101,30 @@ impl HitRecord {
         } else {
             (-1.0) * outward_normal
         };
+        self.geometric_normal = self.normal;
+    }
+
+    // Overrides the geometric normal when it differs from the shading one,
+    // turned to the side of the shading normal
+    pub fn set_geometric_normal(&mut self, n: Vec3) {
+        self.geometric_normal = if n.dot(self.normal) < 0.0 { (-1.0) * n } else { n };
+    }
+
+    // Whether d is above the surface for one normal and below it for the
+    // other. Following such a direction would leak light through the surface.
+    pub fn leaks(&self, d: Vec3) -> bool {
+        (d.dot(self.normal) > 0.0) != (d.dot(self.geometric_normal) > 0.0)
     }
 }
 
 pub trait Hit: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb>;
+
+    // Every span of the whole line of r inside the object, sorted by distance.
+    // Objects that do not enclose a volume have none and can't be used in CSG.
+    fn spans(&self, _r: &Ray) -> Vec<Span> {
+        Vec::new()
+    }
 }
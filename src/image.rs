@@ -0,0 +1,131 @@
+use super::vec::Color;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Image with channels in [0,1] as stored in the file, no gamma decoding
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Image {
+        assert_eq!(pixels.len(), width * height, "pixel count does not match image size");
+        Image { width, height, pixels }
+    }
+
+    // Netpbm images, gray (P2, P5) or color (P3, P6)
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        let data = fs::read(path)?;
+        let mut pos = 0;
+
+        // Header tokens are separated by whitespace and may have # comments in between
+        let next_token = |pos: &mut usize| -> io::Result<String> {
+            loop {
+                while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+                    *pos += 1;
+                }
+                if *pos < data.len() && data[*pos] == b'#' {
+                    while *pos < data.len() && data[*pos] != b'\n' {
+                        *pos += 1;
+                    }
+                } else {
+                    break;
+                }
+            }
+            let start = *pos;
+            while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+                *pos += 1;
+            }
+            if start == *pos {
+                return Err(invalid_data("unexpected end of image"));
+            }
+            Ok(String::from_utf8_lossy(&data[start..*pos]).into_owned())
+        };
+        let next_number = |pos: &mut usize| -> io::Result<usize> {
+            next_token(pos)?
+                .parse::<usize>()
+                .map_err(|_| invalid_data("bad number in image"))
+        };
+
+        let magic = next_token(&mut pos)?;
+        let (channels, binary) = match magic.as_str() {
+            "P2" => (1, false),
+            "P3" => (3, false),
+            "P5" => (1, true),
+            "P6" => (3, true),
+            _ => return Err(invalid_data("only P2, P3, P5 and P6 images are supported")),
+        };
+        let width = next_number(&mut pos)?;
+        let height = next_number(&mut pos)?;
+        let max_value = next_number(&mut pos)?.max(1);
+
+        if width == 0 || height == 0 {
+            return Err(invalid_data("image is empty"));
+        }
+
+        let count = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(channels))
+            .ok_or_else(|| invalid_data("image is too big"))?;
+        let samples: Vec<f32> = if binary {
+            // A single whitespace separates the header from the raster
+            pos += 1;
+            let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+            let end = count
+                .checked_mul(bytes_per_sample)
+                .and_then(|n| n.checked_add(pos))
+                .ok_or_else(|| invalid_data("image is too big"))?;
+            let raster = data
+                .get(pos..end)
+                .ok_or_else(|| invalid_data("image raster is too short"))?;
+            raster
+                .chunks_exact(bytes_per_sample)
+                .map(|b| {
+                    let value = if bytes_per_sample == 2 {
+                        u16::from_be_bytes([b[0], b[1]]) as f32
+                    } else {
+                        b[0] as f32
+                    };
+                    value / max_value as f32
+                })
+                .collect()
+        } else {
+            (0..count)
+                .map(|_| next_number(&mut pos).map(|v| v as f32 / max_value as f32))
+                .collect::<io::Result<Vec<f32>>>()?
+        };
+
+        let pixels = samples
+            .chunks_exact(channels)
+            .map(|c| {
+                if channels == 1 {
+                    Color::new(c[0], c[0], c[0])
+                } else {
+                    Color::new(c[0], c[1], c[2])
+                }
+            })
+            .collect();
+
+        Ok(Image::new(width, height, pixels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Row 0 is the top of the image
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+}
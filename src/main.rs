@@ -1,6 +1,6 @@
 mod vec;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use std::{sync::Arc, cell::RefCell};
+use std::sync::Arc;
 use vec::{Color, Vec3};
 mod hit;
 mod ray;
@@ -9,18 +9,54 @@ use ray::Ray;
 extern crate mini_gl_fb;
 mod camera;
 mod mat;
+mod cuboid;
+mod medium;
+mod volume;
+mod csg;
+mod aabb;
+mod onb;
+mod cylinder;
+mod disk;
+mod torus;
+mod sdf;
+mod image;
+mod heightfield;
+mod texture;
+mod fractal;
+mod bvh;
+mod curve;
+mod mesh;
+mod ply;
+mod stl;
+mod microfacet;
+mod transform;
+mod gltf_import;
+mod pbrt;
+mod spectrum;
 use rand::{self, Rng};
 
 
 use crate::vec::Point3;
 use camera::Camera;
-use hit::{Hit, World, HitRecord};
+use hit::{Hit, World};
 use mat::*;
 use sphere::{MovingSphere, Sphere};
+use cuboid::Cuboid;
+use medium::{Atmosphere, ConstantMedium, Subsurface};
+use volume::{GridMedium, VoxelGrid};
+use csg::Csg;
+use cylinder::{Cone, Cylinder};
+use disk::{Annulus, Disk};
+use torus::Torus;
+use sdf::{Sdf, SdfObject};
+use heightfield::{CellShape, Heightfield};
+use texture::{ColorRamp, ImageTexture, SolidColor, Texture};
+use image::Image;
+use fractal::{Mandelbulb, QuaternionJulia};
+use bvh::Bvh;
+use curve::{Curve, CurveShape};
+use mesh::{Mesh, TriangleMesh};
 
-/*
-TODO! dielectricos con tintado
-*/
 fn random_scene() -> World {
     let mut rng = rand::thread_rng();
     let mut world = World::new();
@@ -57,7 +93,8 @@ fn random_scene() -> World {
                 world.push(Box::new(sphere));
             } else {
                 // Glass
-                let sphere_mat = Arc::new(Dielectric::new(1.5, center.x()));
+                let roughness = rng.gen_range(0.0..0.4);
+                let sphere_mat = Arc::new(Dielectric::new(1.5, roughness));
                 let sphere = Sphere::new(center, 0.2, sphere_mat);
 
                 world.push(Box::new(sphere));
@@ -92,28 +129,782 @@ fn front_spheres() -> World {
     let sphere3 = Sphere::new(Point3::new(-3.0, 0.0, -1.0), 1.0, mat3);
     let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
     let ground_sphere = Sphere::new(Point3::new(0.0, -1002.0, 0.0), 1000.0, ground_mat);
-    let mut world = World::new();
-    world.push(Box::new(sphere1));
-    world.push(Box::new(sphere2));
-    world.push(Box::new(ground_sphere));
-    world.push(Box::new(sphere3));
+    vec![
+        Box::new(sphere1),
+        Box::new(sphere2),
+        Box::new(ground_sphere),
+        Box::new(sphere3),
+    ]
+}
+fn smoke_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    let box_mat = Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0)));
+    let dark_box = Cuboid::new(Point3::new(-1.0, 0.0, -3.0), Point3::new(1.0, 2.5, -1.0), box_mat.clone());
+    let light_box = Cuboid::new(Point3::new(-1.0, 0.0, 1.0), Point3::new(1.0, 1.5, 3.0), box_mat);
+    let smoke_dark = ConstantMedium::new(Box::new(dark_box), 1.5, Color::new(0.0, 0.0, 0.0));
+    let smoke_light = ConstantMedium::new(Box::new(light_box), 1.5, Color::new(1.0, 1.0, 1.0));
+
+    let glass_mat = Arc::new(Dielectric::new(1.5, 0.0));
+    let glass_sphere = Sphere::new(Point3::new(3.0, 1.0, 0.0), 1.0, glass_mat.clone());
+    // A glass shell filled with blue haze
+    let haze_boundary = Sphere::new(Point3::new(3.0, 1.0, 0.0), 0.99, glass_mat);
+    let haze = ConstantMedium::new(Box::new(haze_boundary), 0.8, Color::new(0.2, 0.4, 0.9));
+
+    // Thin mist over the whole scene
+    let mist_boundary = Sphere::new(Point3::new(0.0, 0.0, 0.0), 50.0, Arc::new(Isotropic::new(Color::new(1.0, 1.0, 1.0))));
+    let mist = ConstantMedium::new(Box::new(mist_boundary), 0.005, Color::new(1.0, 1.0, 1.0));
+
+    vec![
+        Box::new(ground_sphere),
+        Box::new(smoke_dark),
+        Box::new(smoke_light),
+        Box::new(glass_sphere),
+        Box::new(haze),
+        Box::new(mist),
+    ]
+}
+
+fn csg_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    // Biconvex lens, the overlap of two big spheres
+    let glass_mat = Arc::new(Dielectric::new(1.5, 0.0));
+    let lens = Csg::intersection(
+        Box::new(Sphere::new(Point3::new(-2.2, 1.2, 0.0), 2.5, glass_mat.clone())),
+        Box::new(Sphere::new(Point3::new(2.2, 1.2, 0.0), 2.5, glass_mat)),
+    );
+
+    // Cube with a spherical dent carved into its front face
+    let red_mat = Arc::new(Lambertian::new(Color::new(0.7, 0.2, 0.1)));
+    let cut_cube = Csg::difference(
+        Box::new(Cuboid::new(Point3::new(-1.0, 0.0, -4.5), Point3::new(1.0, 2.0, -2.5), red_mat.clone())),
+        Box::new(Sphere::new(Point3::new(1.3, 1.0, -3.5), 0.9, red_mat)),
+    );
+
+    // Metal capsule made of two overlapping spheres
+    let metal_mat = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.05));
+    let blob = Csg::union(
+        Box::new(Sphere::new(Point3::new(0.0, 0.8, 2.5), 0.8, metal_mat.clone())),
+        Box::new(Sphere::new(Point3::new(0.0, 0.8, 3.5), 0.8, metal_mat)),
+    );
+
+    vec![Box::new(ground_sphere), Box::new(lens), Box::new(cut_cube), Box::new(blob)]
+}
+
+fn shapes_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    let copper = Arc::new(Metal::new(Color::new(0.8, 0.5, 0.3), 0.1));
+    let blue = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.6)));
+    let green = Arc::new(Lambertian::new(Color::new(0.2, 0.6, 0.2)));
+    let glass = Arc::new(Dielectric::new(1.5, 0.0));
+
+    let cylinder = Cylinder::new(Point3::new(0.0, 0.0, 3.0), Point3::new(0.0, 2.0, 3.0), 0.7, true, copper.clone());
+    let cone = Cone::new(Point3::new(0.0, 0.0, -3.0), Point3::new(0.0, 2.2, -3.0), 0.9, true, blue);
+    let torus = Torus::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.3, 0.0), 0.8, 0.3, glass);
+    let disk = Disk::new(Point3::new(-2.5, 1.5, 0.0), Vec3::new(1.0, 0.2, 0.0), 1.2, green);
+    let annulus = Annulus::new(Point3::new(2.5, 0.01, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.6, 1.0, copper);
+
+    vec![
+        Box::new(ground_sphere),
+        Box::new(cylinder),
+        Box::new(cone),
+        Box::new(torus),
+        Box::new(disk),
+        Box::new(annulus),
+    ]
+}
+
+fn sdf_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    // Two blobs melted together
+    let blob = Sdf::sphere(0.8)
+        .translate(Vec3::new(0.0, 0.8, -0.5))
+        .smooth_union(Sdf::sphere(0.6).translate(Vec3::new(0.0, 1.4, 0.5)), 0.4);
+
+    // Rounded box with a grid of spherical holes
+    let holes = Sdf::sphere(0.15).repeat(Vec3::new(0.0, 0.45, 0.45), [0, 2, 2]);
+    let panel = Sdf::round_box(Vec3::new(0.3, 1.0, 1.0), 0.1)
+        .subtract(holes.translate(Vec3::new(0.3, 0.0, 0.0)))
+        .translate(Vec3::new(0.0, 1.0, -3.2));
+
+    // Twisted column
+    let column = Sdf::round_box(Vec3::new(0.4, 1.2, 0.4), 0.05)
+        .twist(1.2)
+        .translate(Vec3::new(0.0, 1.2, 3.0));
+
+    // Ball resting on a ring
+    let ring = Sdf::torus(0.5, 0.12)
+        .union(Sdf::sphere(0.4).translate(Vec3::new(0.0, 0.3, 0.0)))
+        .translate(Vec3::new(2.0, 0.12, 1.5));
+
+    vec![
+        Box::new(ground_sphere),
+        Box::new(SdfObject::new(blob, Arc::new(Dielectric::new(1.5, 0.0)))),
+        Box::new(SdfObject::new(panel, Arc::new(Lambertian::new(Color::new(0.8, 0.6, 0.2))))),
+        Box::new(SdfObject::new(column, Arc::new(Metal::new(Color::new(0.7, 0.7, 0.8), 0.05)))),
+        Box::new(SdfObject::new(ring, Arc::new(Lambertian::new(Color::new(0.2, 0.3, 0.7))))),
+    ]
+}
+
+fn terrain_scene(path: Option<String>) -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.4, 0.5, 0.3)));
+    let corner = Point3::new(-15.0, -1.5, -15.0);
+    let size = Vec3::new(30.0, 2.5, 30.0);
+
+    let terrain = match path {
+        // Images are usually dense enough for flat triangles
+        Some(path) => Heightfield::from_image(&path, corner, size, CellShape::Triangles, ground_mat)
+            .unwrap_or_else(|e| panic!("could not load {}: {}", path, e)),
+        // Coarse rolling hills, bilinear cells keep them from looking faceted
+        None => {
+            const N: usize = 48;
+            let mut heights = Vec::with_capacity(N * N);
+            for j in 0..N {
+                for i in 0..N {
+                    let x = i as f32 / (N - 1) as f32 * 6.0;
+                    let z = j as f32 / (N - 1) as f32 * 6.0;
+                    let h = 0.5 + 0.25 * (x * 1.3).sin() * (z * 1.1).cos() + 0.1 * (x * 3.7 + z * 2.9).sin();
+                    heights.push(h.clamp(0.0, 1.0));
+                }
+            }
+            Heightfield::new(N, N, heights, corner, size, CellShape::Bilinear, ground_mat)
+        }
+    };
+
+    let metal = Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 0.0));
+    let sphere = Sphere::new(Point3::new(0.0, 1.5, 0.0), 1.0, metal);
+
+    vec![Box::new(terrain), Box::new(sphere)]
+}
+
+fn fractal_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    // Colored by how many iterations each point took to escape
+    let ramp = Arc::new(ColorRamp::new(vec![
+        Color::new(0.1, 0.05, 0.3),
+        Color::new(0.8, 0.2, 0.1),
+        Color::new(1.0, 0.8, 0.3),
+        Color::new(0.9, 0.9, 0.9),
+    ]));
+    let bulb = Mandelbulb::new(Point3::new(0.0, 1.3, -1.6), 1.1, 8.0, 10, Arc::new(Lambertian::textured(ramp.clone())));
+    let julia = QuaternionJulia::new(
+        Point3::new(0.0, 1.3, 1.8),
+        0.9,
+        [-0.125, -0.256, 0.847, 0.0895],
+        0.0,
+        16,
+        Arc::new(Lambertian::textured(ramp)),
+    );
+
+    vec![Box::new(ground_sphere), Box::new(bulb), Box::new(julia)]
+}
+
+fn hair_scene() -> World {
+    let mut rng = rand::thread_rng();
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.4, 0.35, 0.25)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    // Furry ball, strands grow out of the surface and droop under their weight
+    let fur_mat = Arc::new(KajiyaKay::new(Color::new(0.6, 0.35, 0.15), Color::new(0.3, 0.3, 0.3), 40.0, 0.05));
+    let center = Point3::new(0.0, 1.0, 0.0);
+    let down = Vec3::new(0.0, -1.0, 0.0);
+    let mut strands = World::new();
+    for _ in 0..10000 {
+        let n = Vec3::random_in_unit_sphere().unit_vector();
+        let root = center + 0.8 * n;
+        let length = rng.gen_range(0.25..0.4);
+        let jitter = 0.05 * Vec3::random_in_unit_sphere();
+        let cp = [
+            root,
+            root + length / 3.0 * n,
+            root + 2.0 * length / 3.0 * n + 0.08 * down + jitter,
+            root + length * n + 0.2 * down + 2.0 * jitter,
+        ];
+        strands.push(Box::new(Curve::new(cp, 0.012, 0.002, CurveShape::Tube, fur_mat.clone())));
+    }
+
+    // Tapered grass blades bending in random directions
+    let grass_mat = Arc::new(Lambertian::new(Color::new(0.2, 0.5, 0.1)));
+    for _ in 0..3000 {
+        let radius = rng.gen_range(1.2..4.0);
+        let angle = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+        let root = Point3::new(radius * angle.cos(), 0.0, radius * angle.sin());
+        let height = rng.gen_range(0.3..0.7);
+        let bend = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+        let lean = 0.4 * height * Vec3::new(bend.cos(), 0.0, bend.sin());
+        let cp = [
+            root,
+            root + Vec3::new(0.0, height / 2.0, 0.0),
+            root + Vec3::new(0.0, height, 0.0) + 0.5 * lean,
+            root + Vec3::new(0.0, height, 0.0) + lean,
+        ];
+        strands.push(Box::new(Curve::new(cp, 0.04, 0.0, CurveShape::Flat, grass_mat.clone())));
+    }
+
+    let skin = Sphere::new(center, 0.8, Arc::new(Lambertian::new(Color::new(0.3, 0.18, 0.08))));
+
+    vec![Box::new(ground_sphere), Box::new(skin), Box::new(Bvh::new(strands, 0.0, 1.0))]
+}
+
+// Torus with smooth normals and a rainbow of vertex colors around it
+fn rainbow_torus() -> Mesh {
+    const RINGS: usize = 64;
+    const SIDES: usize = 24;
+    let mut mesh = Mesh::default();
+    for i in 0..RINGS {
+        let a = i as f32 / RINGS as f32 * 2.0 * std::f32::consts::PI;
+        let color = Color::new(0.5 + 0.5 * a.cos(), 0.5 + 0.5 * (a + 2.1).cos(), 0.5 + 0.5 * (a + 4.2).cos());
+        for j in 0..SIDES {
+            let b = j as f32 / SIDES as f32 * 2.0 * std::f32::consts::PI;
+            let normal = Vec3::new(a.cos() * b.cos(), b.sin(), a.sin() * b.cos());
+            mesh.positions.push(Point3::new(a.cos(), 0.0, a.sin()) + 0.35 * normal);
+            mesh.normals.push(normal);
+            mesh.colors.push(color);
+
+            let next = |i: usize, j: usize| (i % RINGS) * SIDES + j % SIDES;
+            mesh.triangles.push([next(i, j), next(i, j + 1), next(i + 1, j + 1)]);
+            mesh.triangles.push([next(i, j), next(i + 1, j + 1), next(i + 1, j)]);
+        }
+    }
+    mesh.fit(Point3::new(0.0, 0.0, 0.0), 3.0);
+    mesh
+}
+
+fn mesh_scene(path: Option<String>) -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    let mesh = match path {
+        Some(path) => {
+            let mut mesh = Mesh::load(&path).unwrap_or_else(|e| panic!("could not load {}: {}", path, e));
+            mesh.fit(Point3::new(0.0, 0.0, 0.0), 3.0);
+            mesh
+        }
+        None => rainbow_torus(),
+    };
+
+    // Neutral gray, vertex colors tint it
+    let mat = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+
+    vec![Box::new(ground_sphere), Box::new(TriangleMesh::new(mesh, mat))]
+}
+
+fn metals_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    // Polished metals in front, the same ones rough behind them
+    let mut world: World = vec![Box::new(ground_sphere)];
+    let metals: [fn(f32) -> Conductor; 4] = [Conductor::gold, Conductor::copper, Conductor::aluminum, Conductor::silver];
+    for (i, metal) in metals.iter().enumerate() {
+        let z = 2.4 * (i as f32 - 1.5);
+        world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, z), 1.0, Arc::new(metal(0.05)))));
+        world.push(Box::new(Sphere::new(Point3::new(-3.0, 1.0, z), 1.0, Arc::new(metal(0.5)))));
+    }
+
+    world
+}
+
+fn glass_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Glass from polished to frosted, with colored balls behind to see through
+    for (i, roughness) in [0.0, 0.1, 0.3, 0.6].iter().enumerate() {
+        let z = 2.4 * (i as f32 - 1.5);
+        let glass = Arc::new(Dielectric::new(1.5, *roughness));
+        world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, z), 1.0, glass)));
+
+        let color = Color::new(0.8 * (i % 2) as f32 + 0.1, 0.3 + 0.2 * i as f32, 0.8 - 0.2 * i as f32);
+        let ball = Arc::new(Lambertian::new(color));
+        world.push(Box::new(Sphere::new(Point3::new(-3.5, 0.6, z + 0.5), 0.6, ball)));
+    }
+
+    // Same green glass at growing sizes, thicker ones show a deeper color
+    for (i, radius) in [0.25, 0.45, 0.7].iter().enumerate() {
+        let tint = Arc::new(DielectricTint::with_absorption(1.5, 0.0, Color::new(1.2, 0.15, 0.9)));
+        let z = 2.0 * (i as f32 - 1.0);
+        world.push(Box::new(Sphere::new(Point3::new(3.5, *radius, z), *radius, tint)));
+    }
+
+    world
+}
+
+fn principled_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    let solid = |c: Color| Arc::new(SolidColor::new(c)) as Arc<dyn Texture>;
+    let gold = Color::new(1.0, 0.71, 0.29);
+    let red = Color::new(0.8, 0.1, 0.1);
+    for i in 0..5 {
+        let z = 1.8 * (i as f32 - 2.0);
+        let f = i as f32 / 4.0;
+
+        // Front row goes from dielectric to metal, the middle one from smooth to rough
+        let metallic = Principled::new(solid(gold)).with_metallic(f).with_roughness(0.3);
+        let rough = Principled::new(solid(red)).with_roughness(f);
+        world.push(Box::new(Sphere::new(Point3::new(3.0, 0.8, z), 0.8, Arc::new(metallic))));
+        world.push(Box::new(Sphere::new(Point3::new(0.0, 0.8, z), 0.8, Arc::new(rough))));
+    }
+
+    // Back row: the diffuse, metal and glass special cases, velvet and car paint
+    let back = [
+        Principled::diffuse(Color::new(0.2, 0.6, 0.3)),
+        Principled::metal(Color::new(0.95, 0.93, 0.88), 0.1),
+        Principled::glass(1.5, 0.05),
+        Principled::new(solid(Color::new(0.3, 0.05, 0.3))).with_roughness(1.0).with_sheen(1.0, 0.5),
+        Principled::new(solid(Color::new(0.05, 0.15, 0.6)))
+            .with_metallic(0.6)
+            .with_roughness(0.5)
+            .with_clearcoat(1.0, 0.9),
+    ];
+    for (i, mat) in back.into_iter().enumerate() {
+        let z = 1.8 * (i as f32 - 2.0);
+        world.push(Box::new(Sphere::new(Point3::new(-3.0, 0.8, z), 0.8, Arc::new(mat))));
+    }
+
+    world
+}
+
+fn coated_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Wood like stripes, then a varnish that absorbs blue
+    let browns = [Color::new(0.45, 0.25, 0.1), Color::new(0.3, 0.15, 0.05)];
+    let stripes = Arc::new(ColorRamp::new(browns.iter().cycle().take(24).copied().collect()));
+    let wood = Arc::new(Lambertian::textured(stripes));
+    let varnish = Coated::new(1.5, 0.0, wood.clone()).with_absorption(Color::new(0.5, 2.0, 6.0), 0.1);
+
+    // Car paint: a metallic base under a glossy clear coat
+    let paint = Arc::new(
+        Principled::new(Arc::new(SolidColor::new(Color::new(0.6, 0.05, 0.05))))
+            .with_metallic(0.5)
+            .with_roughness(0.4),
+    );
+    let clear_coat = Coated::new(1.5, 0.05, paint.clone());
+
+    // Bare materials in front, coated behind
+    let bare: [Arc<dyn Scatter>; 2] = [wood, paint];
+    let coated: [Arc<dyn Scatter>; 2] = [Arc::new(varnish), Arc::new(clear_coat)];
+    for (i, (bare, coated)) in bare.into_iter().zip(coated).enumerate() {
+        let z = 3.0 * (i as f32 - 0.5);
+        world.push(Box::new(Sphere::new(Point3::new(2.0, 1.0, z), 1.0, bare)));
+        world.push(Box::new(Sphere::new(Point3::new(-1.5, 1.0, z + 0.6), 1.0, coated)));
+    }
+
+    world
+}
+
+fn mix_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    let steel: Arc<dyn Scatter> = Arc::new(Metal::new(Color::new(0.8, 0.8, 0.85), 0.05));
+    let rust: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.45, 0.15, 0.05)));
+    let paint: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.1, 0.4, 0.7)));
+    let dirt: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.25, 0.2, 0.1)));
+
+    // Rust spreading around the sphere, bands of dirt and a flat half and half blend
+    let spreading = Arc::new(ColorRamp::new(vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)]));
+    let bands: Vec<Color> = (0..16).map(|i| if i % 2 == 0 { Color::new(0.0, 0.0, 0.0) } else { Color::new(0.8, 0.8, 0.8) }).collect();
+    let mixes = [
+        MixMaterial::textured(steel.clone(), rust, spreading),
+        MixMaterial::textured(paint, dirt, Arc::new(ColorRamp::new(bands))),
+        MixMaterial::new(steel, Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))), 0.5),
+    ];
+    for (i, mat) in mixes.into_iter().enumerate() {
+        let z = 2.4 * (i as f32 - 1.0);
+        world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, z), 1.0, Arc::new(mat))));
+    }
+
+    world
+}
+
+fn prism_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Dark wall with thin bright bars, seen through the glass they split into rainbows
+    let wall = Arc::new(Lambertian::new(Color::new(0.05, 0.05, 0.05)));
+    world.push(Box::new(Cuboid::new(Point3::new(-4.5, 0.0, -8.0), Point3::new(-4.0, 6.0, 8.0), wall)));
+    let bar = Arc::new(DiffuseLight::new(Color::new(6.0, 6.0, 6.0), true));
+    for i in 0..13 {
+        let z = 0.8 * (i as f32 - 6.0);
+        let bottom = Point3::new(-3.9, 0.0, z);
+        world.push(Box::new(Cylinder::new(bottom, bottom + Vec3::new(0.0, 6.0, 0.0), 0.04, true, bar.clone())));
+    }
+
+    // Upright triangular prism of flint glass
+    let mut prism = Mesh::default();
+    for y in [0.0, 2.5] {
+        for k in 0..3 {
+            let angle = 2.0 * std::f32::consts::PI * k as f32 / 3.0;
+            prism.positions.push(Point3::new(angle.cos(), y, angle.sin()));
+        }
+    }
+    prism.triangles = vec![[0, 1, 2], [3, 5, 4], [0, 4, 1], [0, 3, 4], [1, 5, 2], [1, 4, 5], [2, 3, 0], [2, 5, 3]];
+    world.push(Box::new(TriangleMesh::new(prism, Arc::new(Dielectric::dispersive(Dispersion::flint_glass(), 0.0)))));
+
+    // A diamond, crown glass and a made up glass with very strong dispersion
+    let gems = [
+        (Dispersion::diamond(), Point3::new(1.0, 0.7, 2.6)),
+        (Dispersion::crown_glass(), Point3::new(1.0, 0.7, -2.6)),
+        (Dispersion::Cauchy(1.5, 0.05), Point3::new(2.5, 0.5, 0.0)),
+    ];
+    for (dispersion, center) in gems {
+        let glass = Arc::new(Dielectric::dispersive(dispersion, 0.0));
+        world.push(Box::new(Sphere::new(center, center.y(), glass)));
+    }
+
+    world
+}
+
+fn film_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.1, 0.1, 0.12)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Soap bubbles, water with the film thickness varying around them
+    let swirl = Arc::new(ColorRamp::new(vec![
+        Color::new(0.3, 0.3, 0.3),
+        Color::new(1.0, 1.0, 1.0),
+        Color::new(0.6, 0.6, 0.6),
+        Color::new(1.4, 1.4, 1.4),
+        Color::new(0.3, 0.3, 0.3),
+    ]));
+    for (center, radius) in [(Point3::new(0.0, 1.2, 0.0), 1.2), (Point3::new(1.5, 0.6, -2.2), 0.6), (Point3::new(2.0, 0.5, 2.0), 0.5)] {
+        let soap = ThinFilm::dielectric(500.0, 1.33, 1.0).with_thickness_texture(swirl.clone());
+        world.push(Box::new(Sphere::new(center, radius, Arc::new(soap))));
+    }
+
+    // Oil on a puddle
+    let oil = ThinFilm::dielectric(350.0, 1.47, 1.33).with_thickness_texture(swirl);
+    world.push(Box::new(Disk::new(Point3::new(0.0, 0.001, 0.0), Vec3::new(0.0, 1.0, 0.0), 6.0, Arc::new(oil))));
+
+    // Anodized titanium, the oxide thickness sets the color
+    let (titanium_eta, titanium_k) = (Color::new(2.7, 2.5, 2.2), Color::new(3.8, 3.4, 2.9));
+    for (thickness, z) in [(60.0, 2.6), (120.0, -2.6)] {
+        let anodized = ThinFilm::conductor(thickness, 2.3, titanium_eta, titanium_k);
+        world.push(Box::new(Sphere::new(Point3::new(-1.5, 0.8, z), 0.8, Arc::new(anodized))));
+    }
+
+    world
+}
+
+fn clay_scene() -> World {
+    let ground_mat = Arc::new(OrenNayar::new(Color::new(0.5, 0.5, 0.5), 30.0));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Lit from behind the camera, rougher clay looks flatter
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0), false));
+    world.push(Box::new(Sphere::new(Point3::new(14.0, 6.0, 6.0), 2.0, light)));
+
+    let clay = Color::new(0.75, 0.45, 0.3);
+    let spheres: [Arc<dyn Scatter>; 4] = [
+        Arc::new(Lambertian::new(clay)),
+        Arc::new(OrenNayar::new(clay, 20.0)),
+        Arc::new(OrenNayar::new(clay, 40.0)),
+        Arc::new(OrenNayar::new(clay, 60.0)),
+    ];
+    for (i, mat) in spheres.into_iter().enumerate() {
+        let z = 2.4 * (i as f32 - 1.5);
+        world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, z), 1.0, mat)));
+    }
+
+    world
+}
+
+fn brushed_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 0.22)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    let light = Arc::new(DiffuseLight::new(Color::new(20.0, 20.0, 20.0), false));
+    world.push(Box::new(Sphere::new(Point3::new(8.0, 8.0, -4.0), 1.5, light)));
+
+    // Brushed around the axis of each shape, the highlights stretch across the brushing
+    let brushed = Arc::new(Conductor::aluminum(0.0).with_anisotropic_roughness(0.05, 0.5));
+    world.push(Box::new(Disk::new(Point3::new(0.0, 0.01, 0.0), Vec3::new(0.0, 1.0, 0.0), 3.0, brushed.clone())));
+    world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, -2.0), 1.0, brushed.clone())));
+    world.push(Box::new(Cylinder::new(Point3::new(0.5, 0.0, 2.0), Point3::new(0.5, 1.8, 2.0), 0.7, true, brushed.clone())));
+    let copper = Arc::new(Conductor::copper(0.0).with_anisotropic_roughness(0.5, 0.05));
+    world.push(Box::new(Torus::new(Point3::new(2.0, 0.35, -0.2), Vec3::new(0.0, 1.0, 0.0), 0.8, 0.3, copper)));
+
+    world
+}
+
+fn subsurface_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.3, 0.3, 0.3)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Behind the objects, so the thin parts glow
+    let light = Arc::new(DiffuseLight::new(Color::new(12.0, 11.0, 10.0), false));
+    world.push(Box::new(Sphere::new(Point3::new(-6.0, 10.0, 0.0), 2.0, light)));
+
+    // The boundary material is never used, the walk replaces it
+    let unused = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0)));
+
+    // Wax candle, skin, jade and marble
+    let candle = Cylinder::new(Point3::new(0.0, 0.0, -2.6), Point3::new(0.0, 2.2, -2.6), 0.6, true, unused.clone());
+    world.push(Box::new(Subsurface::new(Box::new(candle), 1.45, 0.08, Color::new(0.998, 0.99, 0.96))));
+    let skin = Sphere::new(Point3::new(0.0, 0.9, -0.6), 0.9, unused.clone());
+    world.push(Box::new(
+        Subsurface::new(Box::new(skin), 1.4, 0.1, Color::new(0.995, 0.97, 0.94))
+            .with_mean_free_path(Color::new(0.14, 0.1, 0.08))
+            .with_anisotropy(0.8),
+    ));
+    let jade = Torus::new(Point3::new(0.5, 0.35, 1.5), Vec3::new(0.0, 1.0, 0.0), 0.8, 0.35, unused.clone());
+    world.push(Box::new(Subsurface::new(Box::new(jade), 1.6, 0.15, Color::new(0.9, 0.995, 0.92))));
+    let marble = Sphere::new(Point3::new(1.5, 0.5, 3.3), 0.5, unused);
+    world.push(Box::new(Subsurface::new(Box::new(marble), 1.5, 0.03, Color::new(0.999, 0.999, 0.998))));
+
     world
 }
-fn ray_color(r: &Ray, world: &World, depth: u32) -> Color {
+
+// Tangent space normal map of a grid of spherical dimples, square cells of
+// `cell` pixels
+fn dimples_normal_map(columns: usize, rows: usize, cell: usize) -> Image {
+    let (width, height) = (columns * cell, rows * cell);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            // Centered in the cell, v goes up from the bottom row
+            let dx = ((x % cell) as f32 + 0.5) / cell as f32 - 0.5;
+            let dy = 0.5 - ((y % cell) as f32 + 0.5) / cell as f32;
+            let r2 = (dx * dx + dy * dy) / 0.2;
+            // Tilted towards the bottom of the dimple
+            let n = if r2 < 1.0 {
+                Vec3::new(-dx, -dy, (0.2 - dx * dx - dy * dy).sqrt()).unit_vector()
+            } else {
+                Vec3::new(0.0, 0.0, 1.0)
+            };
+            pixels.push(0.5 * (n + Vec3::new(1.0, 1.0, 1.0)));
+        }
+    }
+    Image::new(width, height, pixels)
+}
+
+// Tangent space normal map of a single tile with bevelled edges
+fn tile_normal_map(size: usize) -> Image {
+    const BEVEL: f32 = 0.12;
+    let mut pixels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let fx = (x as f32 + 0.5) / size as f32;
+            let fy = 1.0 - (y as f32 + 0.5) / size as f32;
+            // The bevel of the closest edge leans outwards
+            let edges = [
+                (fx, Vec3::new(-1.0, 0.0, 1.0)),
+                (1.0 - fx, Vec3::new(1.0, 0.0, 1.0)),
+                (fy, Vec3::new(0.0, -1.0, 1.0)),
+                (1.0 - fy, Vec3::new(0.0, 1.0, 1.0)),
+            ];
+            let (d, bevel) = edges.into_iter().fold((1.0, Vec3::new(0.0, 0.0, 1.0)), |a, e| if e.0 < a.0 { e } else { a });
+            let n = if d < BEVEL { bevel.unit_vector() } else { Vec3::new(0.0, 0.0, 1.0) };
+            pixels.push(0.5 * (n + Vec3::new(1.0, 1.0, 1.0)));
+        }
+    }
+    Image::new(size, size, pixels)
+}
+
+fn bumps_scene() -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.4, 0.4, 0.4)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut world: World = vec![Box::new(ground_sphere)];
+
+    // Low and to the side, grazing light shows the relief
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0), false));
+    world.push(Box::new(Sphere::new(Point3::new(6.0, 3.0, 9.0), 1.5, light)));
+
+    // Tiled wall, a mesh with uvs repeating the tile 5 by 2 times
+    let wall = Mesh {
+        positions: vec![
+            Point3::new(-3.0, 0.0, -5.0),
+            Point3::new(-3.0, 0.0, 5.0),
+            Point3::new(-3.0, 4.0, 5.0),
+            Point3::new(-3.0, 4.0, -5.0),
+        ],
+        uvs: vec![(0.0, 0.0), (5.0, 0.0), (5.0, 2.0), (0.0, 2.0)],
+        triangles: vec![[0, 1, 2], [0, 2, 3]],
+        ..Mesh::default()
+    };
+    let tiles = Arc::new(ImageTexture::new(tile_normal_map(128)));
+    let wall_mat = NormalMap::new(tiles, Arc::new(Lambertian::new(Color::new(0.7, 0.6, 0.5))));
+    world.push(Box::new(TriangleMesh::new(wall, Arc::new(wall_mat))));
+
+    // Grooves around a plastic ball from a ramp of heights
+    let ridges = (0..25).map(|i| if i % 2 == 0 { Color::new(0.0, 0.0, 0.0) } else { Color::new(1.0, 1.0, 1.0) });
+    let ridges = Arc::new(ColorRamp::new(ridges.collect()));
+    let plastic = Principled::diffuse(Color::new(0.1, 0.3, 0.7)).with_roughness(0.3);
+    let grooved = BumpMap::new(ridges, 0.1, Arc::new(plastic));
+    world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, -2.4), 1.0, Arc::new(grooved))));
+
+    // Hammered copper, the same dimple size along u and v of the sphere
+    let dimples = Arc::new(ImageTexture::new(dimples_normal_map(16, 8, 32)));
+    let hammered = NormalMap::new(dimples.clone(), Arc::new(Conductor::copper(0.15)));
+    world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, Arc::new(hammered))));
+
+    // Flatter dimples on rough glass
+    let frosted = NormalMap::new(dimples, Arc::new(Dielectric::new(1.5, 0.05))).with_scale(0.5);
+    world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, 2.4), 1.0, Arc::new(frosted))));
+
+    world
+}
+
+fn gltf_scene(path: Option<String>, aspect_ratio: f32) -> (World, SceneSettings) {
+    let path = path.expect("the gltf scene needs a .gltf or .glb file");
+    let (world, camera) = gltf_import::load(&path, aspect_ratio).unwrap_or_else(|e| panic!("could not load {}: {}", path, e));
+
+    // Without a camera in the file, frame the whole scene from the usual direction
+    let camera = camera.or_else(|| {
+        let bounds = world.bounding_box(0.0, 1.0)?;
+        let center = 0.5 * (bounds.min() + bounds.max());
+        let radius = 0.5 * (bounds.max() - bounds.min()).length();
+        let distance = radius / (17.5f32).to_radians().sin();
+        Some(Camera::new(
+            center + distance * Vec3::new(13.0, 2.0, 3.0).unit_vector(),
+            center,
+            Vec3::new(0.0, 1.0, 0.0),
+            35.0,
+            aspect_ratio,
+            0.0,
+            distance,
+            0.0,
+            1.0,
+        ))
+    });
+
+    (
+        world,
+        SceneSettings {
+            camera,
+            ..SceneSettings::default()
+        },
+    )
+}
+
+fn pbrt_scene(path: Option<String>) -> (World, SceneSettings) {
+    let path = path.expect("the pbrt scene needs a .pbrt file");
+    let scene = pbrt::load(&path).unwrap_or_else(|e| panic!("could not load {}: {}", path, e));
+
+    (
+        scene.world,
+        SceneSettings {
+            camera: scene.camera,
+            resolution: Some(scene.resolution),
+            ..SceneSettings::default()
+        },
+    )
+}
+
+// Procedural puff of hot smoke, used when no grid file is given
+fn fireball_grid() -> VoxelGrid {
+    const N: usize = 48;
+    let mut density = Vec::with_capacity(N * N * N);
+    let mut temperature = Vec::with_capacity(N * N * N);
+    for z in 0..N {
+        for y in 0..N {
+            for x in 0..N {
+                let p = Vec3::new(x as f32, y as f32, z as f32) / (N as f32) - Vec3::new(0.5, 0.5, 0.5);
+                let wobble = 0.08 * (p.x() * 23.0).sin() * (p.y() * 17.0).cos() * (p.z() * 19.0).sin();
+                let r = p.length() + wobble;
+                let d = ((0.45 - r) / 0.45).clamp(0.0, 1.0);
+                density.push(d);
+                temperature.push(if r < 0.25 { 1500.0 + 4000.0 * (0.25 - r) / 0.25 } else { 0.0 });
+            }
+        }
+    }
+    VoxelGrid::new(N, N, N, density, Some(temperature), None)
+}
+
+fn voxel_scene(path: Option<String>) -> World {
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    let grid = match path {
+        Some(path) => VoxelGrid::load(&path).unwrap_or_else(|e| panic!("could not load {}: {}", path, e)),
+        None => fireball_grid(),
+    };
+    let smoke = GridMedium::new(grid, Point3::new(-2.0, 0.0, -2.0), Point3::new(2.0, 4.0, 2.0), 6.0, Color::new(0.6, 0.6, 0.6))
+        .with_emission(Color::new(0.0, 0.0, 0.0), 0.02);
+
+    vec![Box::new(ground_sphere), Box::new(smoke)]
+}
+
+#[derive(Default)]
+struct SceneSettings {
+    atmosphere: Option<Atmosphere>,
+    // Replaces the default camera
+    camera: Option<Camera>,
+    // Image width and height, instead of the default
+    resolution: Option<(u32, u32)>,
+    // Radiance traced one wavelength at a time, with RGB colors upsampled
+    spectral: bool,
+}
+
+impl SceneSettings {
+    // In spectral mode colors become their spectrum's value at the ray's wavelength
+    fn spectrum(&self, c: Color, r: &Ray) -> Color {
+        match r.wavelength() {
+            Some(lambda) if self.spectral => {
+                let value = spectrum::rgb_to_spectrum(c, lambda);
+                Color::new(value, value, value)
+            }
+            _ => c,
+        }
+    }
+}
+
+fn ray_color(r: &Ray, world: &World, settings: &SceneSettings, depth: u32) -> Color {
     if depth == 0 {
         return Color::new(0.0, 0.0, 0.0);
     }
 
-    if let Some(rec) = world.hit(r, 0.001, f32::INFINITY) {
-        if let Some((attenuation, scattered)) = rec.mat.scatter(r, &rec) {
-            attenuation * ray_color(&scattered, world, depth - 1)
+    let hit = world.hit(r, 0.001, f32::INFINITY);
+
+    if let Some(atmosphere) = &settings.atmosphere {
+        if let Some((attenuation, scattered)) = atmosphere.scatter(r, hit.as_ref().map(|rec| rec.t)) {
+            let scattered = scattered.inherit_wavelength(r);
+            return settings.spectrum(attenuation, r) * ray_color(&scattered, world, settings, depth - 1);
+        }
+    }
+
+    if let Some(rec) = hit {
+        let emitted = settings.spectrum(rec.mat.emitted(&rec), r);
+        // Smooth shading normals can send paths through the actual surface.
+        // Materials that bend the normal again check against their own.
+        let scattered = rec
+            .mat
+            .scatter(r, &rec)
+            .filter(|(_, scattered)| rec.mat.checks_leaks() || !rec.leaks(scattered.direction()));
+        if let Some((attenuation, scattered)) = scattered {
+            let scattered = scattered.inherit_wavelength(r);
+            emitted + settings.spectrum(attenuation, r) * ray_color(&scattered, world, settings, depth - 1)
         } else {
-            Color::new(0.0, 0.0, 0.0)
+            emitted
         }
     } else {
         let unit_direction = r.direction().unit_vector();
         let t = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+        settings.spectrum((1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0), r)
     }
 }
 
@@ -126,54 +917,139 @@ fn main() {
     const MAX_DEPTH: u32 = 5;
     const CHUNKS:u32 = 20;
 
-    let (mut event_loop, mut fb) = mini_gl_fb::gotta_go_fast("RTXBROS", IMAGE_WIDTH as f64, IMAGE_HEIGHT as f64);
-    let mut buffer = Arc::new(std::sync::Mutex::new(vec![[128u8,0,0,255];(IMAGE_WIDTH*IMAGE_HEIGHT) as usize]));
+    let (world, mut settings) = match std::env::args().nth(1).as_deref() {
+        Some("front") => (front_spheres(), SceneSettings::default()),
+        Some("smoke") => (smoke_scene(), SceneSettings::default()),
+        Some("voxel") => (voxel_scene(std::env::args().nth(2)), SceneSettings::default()),
+        Some("csg") => (csg_scene(), SceneSettings::default()),
+        Some("shapes") => (shapes_scene(), SceneSettings::default()),
+        Some("sdf") => (sdf_scene(), SceneSettings::default()),
+        Some("fractal") => (fractal_scene(), SceneSettings::default()),
+        Some("hair") => (hair_scene(), SceneSettings::default()),
+        Some("mesh") => (mesh_scene(std::env::args().nth(2)), SceneSettings::default()),
+        Some("metals") => (metals_scene(), SceneSettings::default()),
+        Some("glass") => (glass_scene(), SceneSettings::default()),
+        Some("coated") => (coated_scene(), SceneSettings::default()),
+        Some("mix") => (mix_scene(), SceneSettings::default()),
+        Some("film") => (film_scene(), SceneSettings::default()),
+        Some("clay") => (clay_scene(), SceneSettings::default()),
+        Some("brushed") => (brushed_scene(), SceneSettings::default()),
+        Some("subsurface") => (subsurface_scene(), SceneSettings::default()),
+        Some("bumps") => (bumps_scene(), SceneSettings::default()),
+        Some("prism") => {
+            let lookfrom = Point3::new(11.0, 2.0, 0.0);
+            let lookat = Point3::new(0.0, 1.2, 0.0);
+            let camera = Camera::new(
+                lookfrom,
+                lookat,
+                Vec3::new(0.0, 1.0, 0.0),
+                35.0,
+                ASPECT_RATIO,
+                0.0,
+                (lookat - lookfrom).length(),
+                0.0,
+                1.0,
+            );
+            let settings = SceneSettings {
+                camera: Some(camera),
+                ..SceneSettings::default()
+            };
+            (prism_scene(), settings)
+        }
+        Some("principled") => {
+            // From above so the three rows don't hide each other
+            let lookfrom = Point3::new(13.0, 7.0, 0.0);
+            let lookat = Point3::new(0.0, 0.5, 0.0);
+            let camera = Camera::new(
+                lookfrom,
+                lookat,
+                Vec3::new(0.0, 1.0, 0.0),
+                35.0,
+                ASPECT_RATIO,
+                0.0,
+                (lookat - lookfrom).length(),
+                0.0,
+                1.0,
+            );
+            let settings = SceneSettings {
+                camera: Some(camera),
+                ..SceneSettings::default()
+            };
+            (principled_scene(), settings)
+        }
+        Some("pbrt") => pbrt_scene(std::env::args().nth(2)),
+        Some("gltf") => gltf_scene(std::env::args().nth(2), ASPECT_RATIO),
+        Some("terrain") => (terrain_scene(std::env::args().nth(2)), SceneSettings::default()),
+        Some("fog") => (
+            random_scene(),
+            SceneSettings {
+                atmosphere: Some(Atmosphere::new(0.04, Color::new(0.9, 0.9, 0.9), 0.7, 30.0)),
+                ..SceneSettings::default()
+            },
+        ),
+        _ => (random_scene(), SceneSettings::default()),
+    };
+    settings.spectral = std::env::args().any(|a| a == "--spectral");
+    let (image_width, image_height) = settings.resolution.unwrap_or((IMAGE_WIDTH, IMAGE_HEIGHT));
+    let (mut event_loop, mut fb) = mini_gl_fb::gotta_go_fast("RTXBROS", image_width as f64, image_height as f64);
+    let buffer = Arc::new(std::sync::Mutex::new(vec![[128u8,0,0,255];(image_width*image_height) as usize]));
 
-    let world = random_scene();
     //Camara
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        35.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0,
-    );
+    let cam = settings.camera.take().unwrap_or_else(|| {
+        let lookfrom = Point3::new(13.0, 2.0, 3.0);
+        let lookat = Point3::new(0.0, 0.0, 0.0);
+        let vup = Vec3::new(0.0, 1.0, 0.0);
+        let dist_to_focus = 10.0;
+        let aperture = 0.1;
+
+        Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            35.0,
+            ASPECT_RATIO,
+            aperture,
+            dist_to_focus,
+            0.0,
+            1.0,
+        )
+    });
 
     //Salida de ppm
-    println!("P3\n{} {}\n 256", IMAGE_WIDTH, IMAGE_HEIGHT);
+    println!("P3\n{} {}\n 256", image_width, image_height);
     let mut refresh_count = 0;
 
-    for j in (0..IMAGE_HEIGHT).rev() {
+    for j in (0..image_height).rev() {
         //eprintln!("\r{} ", j + 1);
 
-        let scanline: Vec<Color> = (0..IMAGE_WIDTH)
+        let scanline: Vec<Color> = (0..image_width)
             .into_par_iter()
             .map(|i| {
                 let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..SAMPLES_PER_PIXEL {
+                // Wavelengths for spectral mode, one per sample, stratified over the range
+                let wavelength_offset: f32 = rand::thread_rng().gen();
+                for s in 0..SAMPLES_PER_PIXEL {
                     let mut rng = rand::thread_rng();
                     let random_u: f32 = rng.gen();
                     let random_v: f32 = rng.gen();
 
-                    let u = ((i as f32) + random_u) / ((IMAGE_WIDTH - 1) as f32);
-                    let v = ((j as f32) + random_v) / ((IMAGE_HEIGHT - 1) as f32);
+                    let u = ((i as f32) + random_u) / ((image_width - 1) as f32);
+                    let v = ((j as f32) + random_v) / ((image_height - 1) as f32);
 
                     let r = cam.get_ray(u, v);
-                    pixel_color += ray_color(&r, &world, MAX_DEPTH);
+                    if settings.spectral {
+                        let lambda = spectrum::sample_wavelength((wavelength_offset + s as f32 / SAMPLES_PER_PIXEL as f32).fract());
+                        let radiance = ray_color(&r.with_wavelength(Some(lambda)), &world, &settings, MAX_DEPTH).r();
+                        pixel_color += radiance * spectrum::wavelength_to_xyz(lambda);
+                    } else {
+                        pixel_color += ray_color(&r, &world, &settings, MAX_DEPTH);
+                    }
+                }
+                if settings.spectral {
+                    pixel_color = spectrum::xyz_to_balanced_rgb(pixel_color);
                 }
-                buffer.as_ref().lock().unwrap()[j as usize * IMAGE_WIDTH as usize + i as usize] = pixel_color.format_color_to_array(SAMPLES_PER_PIXEL, (0.0,1.0));
-                //buffer[j as usize * IMAGE_WIDTH as usize + i as usize] = pixel_color.format_color_to_array(SAMPLES_PER_PIXEL, (0.0,1.0));
+                buffer.as_ref().lock().unwrap()[j as usize * image_width as usize + i as usize] = pixel_color.format_color_to_array(SAMPLES_PER_PIXEL, (0.0,1.0));
+                //buffer[j as usize * image_width as usize + i as usize] = pixel_color.format_color_to_array(SAMPLES_PER_PIXEL, (0.0,1.0));
                 pixel_color
             })
             .collect();
@@ -183,7 +1059,7 @@ fn main() {
         }
 
         refresh_count += 1;
-        if refresh_count > IMAGE_HEIGHT/CHUNKS{
+        if refresh_count > image_height/CHUNKS{
             refresh_count = 0;
             fb.update_buffer(&buffer.try_lock().unwrap());
         }
@@ -2,21 +2,40 @@ use rand::Rng;
 
 use crate::{
     hit::HitRecord,
+    microfacet,
+    onb::Onb,
     ray::Ray,
+    spectrum,
+    texture::{SolidColor, Texture},
     vec::{Color, Vec3},
 };
+use std::sync::Arc;
 
 pub trait Scatter: Send + Sync {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Whether scatter already drops the paths that go through the geometric
+    // surface, for materials that bend the shading normal themselves
+    fn checks_leaks(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(a: Color) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn textured(a: Arc<dyn Texture>) -> Lambertian {
         Lambertian { albedo: a }
     }
 }
@@ -30,7 +49,63 @@ impl Scatter for Lambertian {
 
         let scattered = Ray::new(rec.p, scatter_direction, r_in.time());
 
-        Some((self.albedo, scattered))
+        // Vertex colors tint the texture, so a white albedo shows them as they are
+        let albedo = self.albedo.value(rec.u, rec.v, rec.p) * rec.vertex_color.unwrap_or(Color::new(1.0, 1.0, 1.0));
+
+        Some((albedo, scattered))
+    }
+}
+
+// Rough diffuse surface made of V shaped Lambertian facets, sigma is the
+// standard deviation of the facet angle in degrees. Zero is Lambertian.
+#[derive(Clone)]
+pub struct OrenNayar {
+    albedo: Arc<dyn Texture>,
+    a: f32,
+    b: f32,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f32) -> OrenNayar {
+        OrenNayar::textured(Arc::new(SolidColor::new(albedo)), sigma)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, sigma: f32) -> OrenNayar {
+        let sigma2 = sigma.to_radians().powi(2);
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl Scatter for OrenNayar {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = Onb::build_from_w(rec.normal);
+        let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+        let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+
+        let mut rng = rand::thread_rng();
+        let wi = microfacet::sample_cosine_hemisphere(rng.gen(), rng.gen());
+
+        // Cosine sampling leaves the albedo times the facet term
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let max_cos = if sin_i > 1e-4 && sin_o > 1e-4 {
+            ((wi.x() * wo.x() + wi.y() * wo.y()) / (sin_i * sin_o)).max(0.0)
+        } else {
+            0.0
+        };
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() {
+            (sin_o, sin_i / wi.z())
+        } else {
+            (sin_i, sin_o / wo.z())
+        };
+        let facets = self.a + self.b * max_cos * sin_alpha * tan_beta;
+
+        let albedo = self.albedo.value(rec.u, rec.v, rec.p) * rec.vertex_color.unwrap_or(Color::new(1.0, 1.0, 1.0));
+        Some((facets * albedo, Ray::new(rec.p, frame.local(wi), r_in.time())))
     }
 }
 
@@ -63,108 +138,1001 @@ impl Scatter for Metal {
     }
 }
 
+// Index of refraction changing with the wavelength, in micrometers for the formulas.
+// Paths carry a single wavelength, not a hero wavelength with companions: at a
+// smooth dispersive interface the companions would bend elsewhere and drop out
+// anyway, so the colors converge one wavelength per path.
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy(f32, f32),
+    // n^2 = 1 + sum of b lambda^2 / (lambda^2 - c)
+    Sellmeier([f32; 3], [f32; 3]),
+}
+
+impl Dispersion {
+    pub fn crown_glass() -> Dispersion {
+        Dispersion::Sellmeier([1.039612, 0.2317923, 1.010469], [0.0060007, 0.0200179, 103.5607])
+    }
+
+    pub fn flint_glass() -> Dispersion {
+        Dispersion::Sellmeier([1.737597, 0.3137473, 1.898781], [0.0131887, 0.0623068, 155.2363])
+    }
+
+    pub fn diamond() -> Dispersion {
+        Dispersion::Sellmeier([0.3306, 4.3356, 0.0], [0.030625, 0.011236, 0.0])
+    }
+
+    pub fn ior(&self, wavelength: f32) -> f32 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy(a, b) => a + b / l2,
+            Dispersion::Sellmeier(b, c) => {
+                let sum: f32 = b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+// Glass, smooth at roughness 0 and GGX microfacets above that. With dispersion
+// the path picks a wavelength at the first hit and keeps it from then on.
 #[derive(Clone, Copy)]
 pub struct Dielectric {
     ir: f32,
-    fuzz: f32,
+    alpha: f32,
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
-    pub fn new(index_of_refraction: f32, fuzz: f32) -> Dielectric {
+    pub fn new(index_of_refraction: f32, roughness: f32) -> Dielectric {
         Dielectric {
             ir: index_of_refraction,
-            fuzz,
+            alpha: dielectric_alpha(roughness),
+            dispersion: None,
         }
     }
 
-    fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
-        let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    pub fn dispersive(dispersion: Dispersion, roughness: f32) -> Dielectric {
+        Dielectric {
+            // At the sodium D line, the usual reference
+            ir: dispersion.ior(589.3),
+            alpha: dielectric_alpha(roughness),
+            dispersion: Some(dispersion),
+        }
     }
 }
 
 impl Scatter for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
+        let white = Color::new(1.0, 1.0, 1.0);
+        let (ir, tint, wavelength) = match (self.dispersion, r_in.wavelength()) {
+            (None, wavelength) => (self.ir, white, wavelength),
+            (Some(d), Some(wavelength)) => (d.ior(wavelength), white, Some(wavelength)),
+            (Some(d), None) => {
+                let wavelength = spectrum::sample_wavelength(rand::thread_rng().gen());
+                (d.ior(wavelength), spectrum::wavelength_to_rgb(wavelength), Some(wavelength))
+            }
+        };
+
+        let (weight, direction) = scatter_dielectric(ir, self.alpha, r_in, rec)?;
+        let scattered = Ray::new(rec.p, direction, r_in.time()).with_wavelength(wavelength);
+        Some((weight * tint, scattered))
+    }
+}
+
+// Colored glass absorbing light inside following Beer-Lambert, so thick parts
+// look deeper than thin ones. Every path inside ends at a back face hit, where
+// the distance travelled since entering (or the last internal bounce) is known.
+#[derive(Clone, Copy)]
+pub struct DielectricTint {
+    ir: f32,
+    alpha: f32,
+    absorption: Color,
+}
+
+impl DielectricTint {
+    // White light turns into color after one unit of distance inside
+    pub fn new(index_of_refraction: f32, roughness: f32, color: Color) -> DielectricTint {
+        DielectricTint::with_color_at_distance(index_of_refraction, roughness, color, 1.0)
+    }
+
+    // Absorption coefficient per unit of distance, for each channel
+    pub fn with_absorption(index_of_refraction: f32, roughness: f32, absorption: Color) -> DielectricTint {
+        DielectricTint {
+            ir: index_of_refraction,
+            alpha: dielectric_alpha(roughness),
+            absorption,
+        }
+    }
+
+    // Coefficient such that white light turns into color after distance
+    pub fn with_color_at_distance(index_of_refraction: f32, roughness: f32, color: Color, distance: f32) -> DielectricTint {
+        let sigma = |c: f32| -c.clamp(1e-4, 1.0).ln() / distance.max(1e-4);
+        let absorption = Color::new(sigma(color.r()), sigma(color.g()), sigma(color.b()));
+        DielectricTint::with_absorption(index_of_refraction, roughness, absorption)
+    }
+}
+
+impl Scatter for DielectricTint {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (weight, direction) = scatter_dielectric(self.ir, self.alpha, r_in, rec)?;
+
+        let transmittance = if rec.front_face {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            let distance = rec.t * r_in.direction().length();
+            let beer = |sigma: f32| (-sigma * distance).exp();
+            Color::new(beer(self.absorption.r()), beer(self.absorption.g()), beer(self.absorption.b()))
+        };
+
+        Some((weight * transmittance, Ray::new(rec.p, direction, r_in.time())))
+    }
+}
+
+// Zero keeps the perfectly smooth interface
+fn dielectric_alpha(roughness: f32) -> f32 {
+    let roughness = roughness.abs().min(1.0);
+    if roughness == 0.0 {
+        0.0
+    } else {
+        microfacet::roughness_to_alpha(roughness)
+    }
+}
+
+// Reflects or refracts following the Fresnel term, of the surface normal when
+// smooth and of a visible GGX normal when rough. Returns the throughput weight
+// and the new direction.
+fn scatter_dielectric(ir: f32, alpha: f32, r_in: &Ray, rec: &HitRecord) -> Option<(f32, Vec3)> {
+    // The normal always faces the incoming ray, eta is from its side to the other
+    let eta = if rec.front_face { ir } else { 1.0 / ir };
+    let frame = Onb::build_from_w(rec.normal);
+    let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+    let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+
+    let (weight, wi) = sample_dielectric(wo, eta, alpha)?;
+    Some((weight, frame.local(wi)))
+}
+
+// Reflection or refraction of wo in the local frame of the interface, through
+// a sampled microfacet when it is rough. Returns the weight and wi, above the
+// interface when reflected and below it when refracted.
+fn sample_dielectric(wo: Vec3, eta: f32, alpha: f32) -> Option<(f32, Vec3)> {
+    let mut rng = rand::thread_rng();
+    let h = if alpha == 0.0 {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        microfacet::sample_visible_normal(wo, (alpha, alpha), rng.gen(), rng.gen())
+    };
+
+    // Picking reflection with probability F cancels the Fresnel factor
+    let reflectance = microfacet::fresnel_dielectric(wo.dot(h), eta);
+    let wi = if rng.gen::<f32>() < reflectance {
+        let wi = ((-1.0) * wo).reflect(h);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        wi
+    } else {
+        let wi = microfacet::refract(wo, h, eta)?;
+        if wi.z() >= 0.0 {
+            return None;
+        }
+        wi
+    };
+
+    let weight = if alpha == 0.0 {
+        1.0
+    } else {
+        microfacet::smith_g2(wo, wi, (alpha, alpha)) / microfacet::smith_g1(wo, (alpha, alpha))
+    };
+    Some((weight, wi))
+}
+
+#[derive(Clone, Copy)]
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(a: Color) -> Isotropic {
+        Isotropic { albedo: a }
+    }
+}
+
+impl Scatter for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let scattered = Ray::new(rec.p, Vec3::random_in_unit_sphere().unit_vector(), r_in.time());
+
+        Some((self.albedo, scattered))
+    }
+}
+
+// Kajiya-Kay hair shading around the strand tangent (dpdu). The specular lobe
+// follows the cone of mirror directions, tilted by shift radians like the
+// scales of a hair cuticle.
+#[derive(Clone, Copy)]
+pub struct KajiyaKay {
+    diffuse: Color,
+    specular: Color,
+    exponent: f32,
+    shift: f32,
+}
+
+impl KajiyaKay {
+    pub fn new(diffuse: Color, specular: Color, exponent: f32, shift: f32) -> KajiyaKay {
+        KajiyaKay {
+            diffuse,
+            specular,
+            exponent,
+            shift,
+        }
+    }
+
+    fn luminance(c: Color) -> f32 {
+        0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b()
+    }
+}
+
+impl Scatter for KajiyaKay {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let tangent = if rec.dpdu.near_zero() {
+            Onb::build_from_w(rec.normal).local(Vec3::new(1.0, 0.0, 0.0))
         } else {
-            self.ir
+            rec.dpdu.unit_vector()
         };
+        let frame = Onb::build_from_w(tangent);
 
-        let unit_direction = r_in.direction().unit_vector();
-        let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+        let diffuse_weight = Self::luminance(self.diffuse);
+        let specular_weight = Self::luminance(self.specular);
+        if diffuse_weight + specular_weight <= 0.0 {
+            return None;
+        }
+        let p_specular = specular_weight / (diffuse_weight + specular_weight);
 
         let mut rng = rand::thread_rng();
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let will_reflect = rng.gen::<f32>() < Self::reflectance(cos_theta, refraction_ratio);
+        let (attenuation, direction) = if rng.gen::<f32>() < p_specular {
+            // Longitudinal angle mirrored around the normal plane, spread by
+            // a gaussian that matches cos^exponent near its peak
+            let wo = (-1.0) * r_in.direction().unit_vector();
+            let theta_o = wo.dot(tangent).clamp(-1.0, 1.0).asin();
+            let (u1, u2) = (rng.gen::<f32>().max(1e-6), rng.gen::<f32>());
+            let gaussian = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos();
+            let theta_i = -theta_o + 2.0 * self.shift + gaussian / self.exponent.max(1.0).sqrt();
+            let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
 
-        let direction = if cannot_refract || will_reflect {
-            unit_direction.reflect(rec.normal)
+            let direction = frame.local(Vec3::new(
+                theta_i.cos() * phi.cos(),
+                theta_i.cos() * phi.sin(),
+                theta_i.sin(),
+            ));
+            (self.specular / p_specular, direction)
         } else {
-            unit_direction.refract(rec.normal, refraction_ratio)
+            // Diffuse term proportional to the sine with the tangent, sampled
+            // uniformly over the sphere as light passes around thin strands
+            let direction = Vec3::random_in_unit_sphere().unit_vector();
+            let sine = (1.0 - direction.dot(tangent).powi(2)).max(0.0).sqrt();
+            let weight = 4.0 / std::f32::consts::PI * sine / (1.0 - p_specular);
+            (weight * self.diffuse, direction)
         };
 
-        let scattered = Ray::new(
-            rec.p,
-            direction + self.fuzz * Vec3::random_in_unit_sphere(),
-            r_in.time(),
-        );
+        Some((attenuation, Ray::new(rec.p, direction, r_in.time())))
+    }
+}
 
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+// glTF metallic-roughness: GGX specular over a diffuse base that fades out
+// as the surface becomes metallic. A metallic-roughness texture scales the
+// factors with its blue (metallic) and green (roughness) channels.
+pub struct MetallicRoughness {
+    base_color: Arc<dyn Texture>,
+    metallic: f32,
+    roughness: f32,
+    metallic_roughness: Arc<dyn Texture>,
+    emission: Arc<dyn Texture>,
+}
+
+impl MetallicRoughness {
+    pub fn new(base_color: Arc<dyn Texture>, metallic: f32, roughness: f32) -> MetallicRoughness {
+        MetallicRoughness {
+            base_color,
+            metallic,
+            roughness,
+            metallic_roughness: Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))),
+            emission: Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))),
+        }
+    }
+
+    pub fn with_metallic_roughness_texture(mut self, t: Arc<dyn Texture>) -> MetallicRoughness {
+        self.metallic_roughness = t;
+        self
+    }
+
+    pub fn with_emission(mut self, t: Arc<dyn Texture>) -> MetallicRoughness {
+        self.emission = t;
+        self
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct DielectricTint {
+impl Scatter for MetallicRoughness {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let base = self.base_color.value(rec.u, rec.v, rec.p) * rec.vertex_color.unwrap_or(white);
+        let factors = self.metallic_roughness.value(rec.u, rec.v, rec.p);
+        let metallic = (self.metallic * factors.b()).clamp(0.0, 1.0);
+        let alpha = microfacet::roughness_to_alpha((self.roughness * factors.g()).clamp(0.0, 1.0));
+
+        let frame = Onb::build_from_w(rec.normal);
+        let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+        let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+        let f0 = (1.0 - metallic) * Color::new(0.04, 0.04, 0.04) + metallic * base;
+
+        let mut rng = rand::thread_rng();
+        let p_specular = 0.5 + 0.5 * metallic;
+        let (attenuation, wi) = if rng.gen::<f32>() < p_specular {
+            let h = microfacet::sample_visible_normal(wo, (alpha, alpha), rng.gen(), rng.gen());
+            let wi = ((-1.0) * wo).reflect(h);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            let weight = microfacet::smith_g2(wo, wi, (alpha, alpha)) / microfacet::smith_g1(wo, (alpha, alpha));
+            (weight / p_specular * microfacet::schlick(f0, wo.dot(h)), wi)
+        } else {
+            let wi = microfacet::sample_cosine_hemisphere(rng.gen(), rng.gen());
+            let diffuse = (1.0 - metallic) * base * (white - microfacet::schlick(f0, wo.z()));
+            (diffuse / (1.0 - p_specular), wi)
+        };
+
+        Some((attenuation, Ray::new(rec.p, frame.local(wi), r_in.time())))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.emission.value(rec.u, rec.v, rec.p)
+    }
+}
+
+// Disney style principled BSDF. One lobe is picked per bounce: a clearcoat
+// on top, then metal, glass or a specular layer over a diffuse base with sheen.
+// Lambertian, Metal and Dielectric are the diffuse, metal and glass special cases.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_gloss: f32,
+    transmission: f32,
+    ior: f32,
+}
+
+impl Principled {
+    pub fn new(base_color: Arc<dyn Texture>) -> Principled {
+        Principled {
+            base_color,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+
+    pub fn diffuse(c: Color) -> Principled {
+        Principled::new(Arc::new(SolidColor::new(c))).with_specular(0.0, 0.0)
+    }
+
+    pub fn metal(c: Color, roughness: f32) -> Principled {
+        Principled::new(Arc::new(SolidColor::new(c)))
+            .with_metallic(1.0)
+            .with_roughness(roughness)
+    }
+
+    pub fn glass(index_of_refraction: f32, roughness: f32) -> Principled {
+        Principled::new(Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))))
+            .with_roughness(roughness)
+            .with_transmission(1.0, index_of_refraction)
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> Principled {
+        self.metallic = metallic.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Principled {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    // Specular 0.5 is a reflectance of 4%, tint colors it towards the base color
+    pub fn with_specular(mut self, specular: f32, tint: f32) -> Principled {
+        self.specular = specular.max(0.0);
+        self.specular_tint = tint.clamp(0.0, 1.0);
+        self
+    }
+
+    // Grazing retro reflection for cloth
+    pub fn with_sheen(mut self, sheen: f32, tint: f32) -> Principled {
+        self.sheen = sheen.max(0.0);
+        self.sheen_tint = tint.clamp(0.0, 1.0);
+        self
+    }
+
+    // Colorless glossy coat, gloss 1 is mirror like
+    pub fn with_clearcoat(mut self, clearcoat: f32, gloss: f32) -> Principled {
+        self.clearcoat = clearcoat.clamp(0.0, 1.0);
+        self.clearcoat_gloss = gloss.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: f32, index_of_refraction: f32) -> Principled {
+        self.transmission = transmission.clamp(0.0, 1.0);
+        self.ior = index_of_refraction;
+        self
+    }
+
+    // Reflection off a visible GGX normal, None when it goes below the surface
+    fn reflect_ggx(wo: Vec3, alpha: f32, rng: &mut impl Rng) -> Option<(Vec3, Vec3, f32)> {
+        let h = microfacet::sample_visible_normal(wo, (alpha, alpha), rng.gen(), rng.gen());
+        let wi = ((-1.0) * wo).reflect(h);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        let weight = microfacet::smith_g2(wo, wi, (alpha, alpha)) / microfacet::smith_g1(wo, (alpha, alpha));
+        Some((h, wi, weight))
+    }
+}
+
+impl Scatter for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let base = self.base_color.value(rec.u, rec.v, rec.p) * rec.vertex_color.unwrap_or(white);
+        let luminance = 0.2126 * base.r() + 0.7152 * base.g() + 0.0722 * base.b();
+        let tint = if luminance > 0.0 { base / luminance } else { white };
+
+        let frame = Onb::build_from_w(rec.normal);
+        let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+        let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+        let alpha = microfacet::roughness_to_alpha(self.roughness);
+
+        // Each lobe is picked with the probability of its share of the energy,
+        // so the share cancels out of the weight
+        let mut rng = rand::thread_rng();
+        let coat = self.clearcoat * microfacet::schlick(Color::new(0.04, 0.04, 0.04), wo.z()).r();
+        let (attenuation, wi) = if rng.gen::<f32>() < coat {
+            // Like Disney, the gloss mixes the alpha itself, not a roughness
+            let coat_alpha = 0.1 + (0.001 - 0.1) * self.clearcoat_gloss;
+            let (_, wi, weight) = Principled::reflect_ggx(wo, coat_alpha, &mut rng)?;
+            (Color::new(weight, weight, weight), wi)
+        } else if rng.gen::<f32>() < self.metallic {
+            let (h, wi, weight) = Principled::reflect_ggx(wo, alpha, &mut rng)?;
+            (weight * microfacet::schlick(base, wo.dot(h)), wi)
+        } else if rng.gen::<f32>() < self.transmission {
+            let (weight, direction) = scatter_dielectric(self.ior, dielectric_alpha(self.roughness), r_in, rec)?;
+            let scattered = Ray::new(rec.p, direction, r_in.time());
+            return Some((weight * base, scattered));
+        } else {
+            let f0 = 0.08 * self.specular * ((1.0 - self.specular_tint) * white + self.specular_tint * tint);
+            let specular = microfacet::schlick(f0, wo.z());
+            let p_specular = ((specular.r() + specular.g() + specular.b()) / 3.0).clamp(0.0, 1.0);
+            if rng.gen::<f32>() < p_specular {
+                let (h, wi, weight) = Principled::reflect_ggx(wo, alpha, &mut rng)?;
+                (weight / p_specular * microfacet::schlick(f0, wo.dot(h)), wi)
+            } else {
+                let wi = microfacet::sample_cosine_hemisphere(rng.gen(), rng.gen());
+                let cos_d = (wo + wi).unit_vector().dot(wi);
+                let schlick_weight = |cos: f32| (1.0 - cos).clamp(0.0, 1.0).powi(5);
+
+                // Disney diffuse with its roughness dependent retro reflection
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z())) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+                let sheen_color = self.sheen * ((1.0 - self.sheen_tint) * white + self.sheen_tint * tint);
+                let sheen = std::f32::consts::PI * schlick_weight(cos_d) * sheen_color;
+                (fd * base + sheen, wi)
+            }
+        };
+
+        Some((attenuation, Ray::new(rec.p, frame.local(wi), r_in.time())))
+    }
+}
+
+// Paths bouncing inside a coat longer than this are dropped
+const COAT_BOUNCES: usize = 8;
+
+// Any material under a clear dielectric coat, like car paint or varnish.
+// Light either reflects off the coat, following its Fresnel term, or refracts
+// through the smooth or rough interface down to the base and back out, losing
+// what the coat absorbs on the way. The coat is thin so all of it happens at
+// the hit point.
+pub struct Coated {
     ir: f32,
-    fuzz: f32,
-    albedo: Color,
+    alpha: f32,
+    absorption: Color,
+    thickness: f32,
+    base: Arc<dyn Scatter>,
 }
 
-impl DielectricTint {
-    pub fn new(index_of_refraction: f32, fuzz: f32, albedo: Color) -> DielectricTint {
-        DielectricTint {
+impl Coated {
+    pub fn new(index_of_refraction: f32, roughness: f32, m: Arc<dyn Scatter>) -> Coated {
+        Coated {
             ir: index_of_refraction,
-            fuzz,
-            albedo,
+            alpha: dielectric_alpha(roughness),
+            absorption: Color::new(0.0, 0.0, 0.0),
+            thickness: 0.0,
+            base: m,
         }
     }
 
-    fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
-        let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    // Beer-Lambert absorption coefficient of the coat and its thickness
+    pub fn with_absorption(mut self, absorption: Color, thickness: f32) -> Coated {
+        self.absorption = absorption;
+        self.thickness = thickness.max(0.0);
+        self
+    }
+
+    fn transmittance(&self, cos_t: f32) -> Color {
+        let distance = self.thickness / cos_t.max(1e-4);
+        let beer = |sigma: f32| (-sigma * distance).exp();
+        Color::new(beer(self.absorption.r()), beer(self.absorption.g()), beer(self.absorption.b()))
     }
 }
 
-impl Scatter for DielectricTint {
+impl Scatter for Coated {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
+        // From inside the object there is no coat to cross
+        if !rec.front_face {
+            return self.base.scatter(r_in, rec);
+        }
+
+        let frame = Onb::build_from_w(rec.normal);
+        let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+        let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+
+        // Off the coat, or down through it to the base
+        let (weight, mut down) = sample_dielectric(wo, self.ir, self.alpha)?;
+        if down.z() > 0.0 {
+            return Some((Color::new(weight, weight, weight), Ray::new(rec.p, frame.local(down), r_in.time())));
+        }
+
+        // Bouncing between the base and the underside of the coat until the
+        // path gets out. Seen from below the coat is the same interface
+        // mirrored, with the inverse index.
+        let mirror = |w: Vec3| Vec3::new(w.x(), w.y(), -w.z());
+        let mut throughput = Color::new(weight, weight, weight);
+        for _ in 0..COAT_BOUNCES {
+            let inner = Ray::new(r_in.origin(), frame.local(down), r_in.time());
+            let (attenuation, scattered) = self.base.scatter(&inner, rec)?;
+            throughput *= attenuation * self.transmittance(-down.z());
+
+            // Transmitted by the base, it leaves the coat below
+            let up = frame.world_to_local(scattered.direction().unit_vector());
+            if up.z() <= 0.0 {
+                return Some((throughput, scattered));
+            }
+
+            throughput *= self.transmittance(up.z());
+            let (weight, wi) = sample_dielectric(mirror((-1.0) * up), 1.0 / self.ir, self.alpha)?;
+            throughput *= weight;
+            if wi.z() < 0.0 {
+                return Some((throughput, Ray::new(rec.p, frame.local(mirror(wi)), r_in.time())));
+            }
+            down = mirror(wi);
+        }
+
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+}
+
+// Picks one of two materials at random per hit, the second one with the
+// probability given by the weight texture (its average channel)
+pub struct MixMaterial {
+    first: Arc<dyn Scatter>,
+    second: Arc<dyn Scatter>,
+    weight: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(first: Arc<dyn Scatter>, second: Arc<dyn Scatter>, weight: f32) -> MixMaterial {
+        MixMaterial::textured(first, second, Arc::new(SolidColor::new(Color::new(weight, weight, weight))))
+    }
+
+    pub fn textured(first: Arc<dyn Scatter>, second: Arc<dyn Scatter>, weight: Arc<dyn Texture>) -> MixMaterial {
+        MixMaterial { first, second, weight }
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f32 {
+        let w = self.weight.value(rec.u, rec.v, rec.p);
+        ((w.r() + w.g() + w.b()) / 3.0).clamp(0.0, 1.0)
+    }
+}
+
+impl Scatter for MixMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let picked = if rand::thread_rng().gen::<f32>() < self.weight(rec) {
+            &self.second
+        } else {
+            &self.first
+        };
+        let (attenuation, scattered) = picked.scatter(r_in, rec)?;
+        // Claiming the check for both, the one that doesn't do it gets it here
+        if self.checks_leaks() && !picked.checks_leaks() && rec.leaks(scattered.direction()) {
+            return None;
+        }
+        Some((attenuation, scattered))
+    }
+
+    fn checks_leaks(&self) -> bool {
+        self.first.checks_leaks() || self.second.checks_leaks()
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let w = self.weight(rec);
+        (1.0 - w) * self.first.emitted(rec) + w * self.second.emitted(rec)
+    }
+}
+
+// Outward shading normal and the tangents of the hit along u and v, None on
+// surfaces without a parameterization
+fn tangent_frame(rec: &HitRecord) -> Option<(Vec3, Vec3, Vec3)> {
+    if rec.dpdu.near_zero() || rec.dpdv.near_zero() {
+        return None;
+    }
+    Some((outward(rec), rec.dpdu, rec.dpdv))
+}
+
+// Shading normal of the hit on the outside of the surface
+fn outward(rec: &HitRecord) -> Vec3 {
+    if rec.front_face {
+        rec.normal
+    } else {
+        (-1.0) * rec.normal
+    }
+}
+
+// Scatters off base with the shading normal swapped for n (outward), and drops
+// the paths that would go through the geometric surface
+fn scatter_shaded(base: &dyn Scatter, r_in: &Ray, rec: &HitRecord, n: Vec3) -> Option<(Color, Ray)> {
+    let mut shaded = rec.clone();
+    shaded.normal = if rec.front_face { n } else { (-1.0) * n };
+    let (attenuation, scattered) = base.scatter(r_in, &shaded)?;
+    if shaded.leaks(scattered.direction()) {
+        return None;
+    }
+    Some((attenuation, scattered))
+}
+
+// Tangent space normal map: red along dpdu, green along dpdv and blue along
+// the normal, each mapped from [0, 1] to [-1, 1]. The scale flattens or
+// strengthens the bumps like the glTF normal texture scale.
+pub struct NormalMap {
+    texture: Arc<dyn Texture>,
+    scale: f32,
+    base: Arc<dyn Scatter>,
+}
+
+impl NormalMap {
+    pub fn new(texture: Arc<dyn Texture>, base: Arc<dyn Scatter>) -> NormalMap {
+        NormalMap { texture, scale: 1.0, base }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    fn shading_normal(&self, rec: &HitRecord) -> Option<Vec3> {
+        let (n, dpdu, dpdv) = tangent_frame(rec)?;
+        let tangent = dpdu - dpdu.dot(n) * n;
+        if tangent.near_zero() {
+            return None;
+        }
+        let tangent = tangent.unit_vector();
+        // Mirrored uvs flip the bitangent
+        let bitangent = if n.cross(tangent).dot(dpdv) < 0.0 {
+            tangent.cross(n)
         } else {
-            self.ir
+            n.cross(tangent)
         };
 
-        let unit_direction = r_in.direction().unit_vector();
-        let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+        let c = self.texture.value(rec.u, rec.v, rec.p);
+        let local = Vec3::new(self.scale * (2.0 * c.r() - 1.0), self.scale * (2.0 * c.g() - 1.0), 2.0 * c.b() - 1.0);
+        let mapped = local.x() * tangent + local.y() * bitangent + local.z() * n;
+        if mapped.near_zero() {
+            return None;
+        }
+        Some(mapped.unit_vector())
+    }
+}
+
+impl Scatter for NormalMap {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        match self.shading_normal(rec) {
+            Some(n) => scatter_shaded(self.base.as_ref(), r_in, rec, n),
+            None => scatter_shaded(self.base.as_ref(), r_in, rec, outward(rec)),
+        }
+    }
+
+    fn checks_leaks(&self) -> bool {
+        true
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+}
+
+// Step in u and v for the finite differences of the height
+const BUMP_DELTA: f32 = 0.0005;
+
+// Grayscale bump map: the average channel of the texture times the scale is a
+// height along the normal, and the shading normal is the one of the surface
+// displaced by it. Nothing moves, only the shading changes.
+pub struct BumpMap {
+    height: Arc<dyn Texture>,
+    scale: f32,
+    base: Arc<dyn Scatter>,
+}
+
+impl BumpMap {
+    pub fn new(height: Arc<dyn Texture>, scale: f32, base: Arc<dyn Scatter>) -> BumpMap {
+        BumpMap { height, scale, base }
+    }
+
+    fn height(&self, u: f32, v: f32, p: Vec3) -> f32 {
+        let c = self.height.value(u, v, p);
+        self.scale * (c.r() + c.g() + c.b()) / 3.0
+    }
+
+    fn shading_normal(&self, rec: &HitRecord) -> Option<Vec3> {
+        let (n, dpdu, dpdv) = tangent_frame(rec)?;
+        let h = self.height(rec.u, rec.v, rec.p);
+        let dhdu = (self.height(rec.u + BUMP_DELTA, rec.v, rec.p + BUMP_DELTA * dpdu) - h) / BUMP_DELTA;
+        let dhdv = (self.height(rec.u, rec.v + BUMP_DELTA, rec.p + BUMP_DELTA * dpdv) - h) / BUMP_DELTA;
+
+        let bumped = (dpdu + dhdu * n).cross(dpdv + dhdv * n);
+        if bumped.near_zero() {
+            return None;
+        }
+        // Keep the side of the original normal whatever the handedness of the uvs
+        let bumped = bumped.unit_vector();
+        Some(if bumped.dot(n) < 0.0 { (-1.0) * bumped } else { bumped })
+    }
+}
+
+impl Scatter for BumpMap {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        match self.shading_normal(rec) {
+            Some(n) => scatter_shaded(self.base.as_ref(), r_in, rec, n),
+            None => scatter_shaded(self.base.as_ref(), r_in, rec, outward(rec)),
+        }
+    }
+
+    fn checks_leaks(&self) -> bool {
+        true
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+}
+
+// Emits light from the front of the surface, or both sides when two sided
+#[derive(Clone, Copy)]
+pub struct DiffuseLight {
+    emit: Color,
+    two_sided: bool,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color, two_sided: bool) -> DiffuseLight {
+        DiffuseLight { emit, two_sided }
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if rec.front_face || self.two_sided {
+            self.emit
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+// GGX microfacet conductor with a complex index of refraction per channel,
+// sampled from the visible normals. Anisotropic roughness follows the
+// surface tangents, x along dpdu and y along dpdv.
+#[derive(Clone, Copy)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    alpha: (f32, f32),
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f32) -> Conductor {
+        let alpha = microfacet::roughness_to_alpha(roughness);
+        Conductor { eta, k, alpha: (alpha, alpha) }
+    }
+
+    // Brushed metal, rough across the brushing direction and smooth along it
+    pub fn with_anisotropic_roughness(mut self, along_u: f32, along_v: f32) -> Conductor {
+        self.alpha = (microfacet::roughness_to_alpha(along_u), microfacet::roughness_to_alpha(along_v));
+        self
+    }
+
+    // Indices at about 650, 550 and 450 nm
+    pub fn gold(roughness: f32) -> Conductor {
+        Conductor::new(Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603), roughness)
+    }
+
+    pub fn copper(roughness: f32) -> Conductor {
+        Conductor::new(Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142), roughness)
+    }
+
+    pub fn aluminum(roughness: f32) -> Conductor {
+        Conductor::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837), roughness)
+    }
+
+    pub fn silver(roughness: f32) -> Conductor {
+        Conductor::new(Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147), roughness)
+    }
+}
+
+impl Scatter for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = Onb::build_from_w_and_tangent(rec.normal, rec.dpdu);
+        let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+        let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+        let alpha = self.alpha;
 
         let mut rng = rand::thread_rng();
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let will_reflect = rng.gen::<f32>() < Self::reflectance(cos_theta, refraction_ratio);
+        let h = microfacet::sample_visible_normal(wo, alpha, rng.gen(), rng.gen());
+        let wi = ((-1.0) * wo).reflect(h);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // With visible normal sampling D and the cosines cancel out
+        let weight = microfacet::smith_g2(wo, wi, alpha) / microfacet::smith_g1(wo, alpha);
+        let attenuation = weight * microfacet::fresnel_conductor(wo.dot(h), self.eta, self.k);
+
+        Some((attenuation, Ray::new(rec.p, frame.local(wi), r_in.time())))
+    }
+}
+
+// Wavelengths the film reflectance is averaged over for RGB paths
+const THIN_FILM_SAMPLES: usize = 16;
+
+enum Substrate {
+    Dielectric(f32),
+    Conductor(Color, Color),
+}
+
+// Thin coating over smooth glass or metal, like soap bubbles, oil on water or
+// anodized metal. Waves reflected at the top and at the bottom of the film
+// interfere, so its reflectance depends on the wavelength. Paths that carry a
+// wavelength use it, the others average the film over the visible range.
+pub struct ThinFilm {
+    thickness: f32,
+    thickness_texture: Arc<dyn Texture>,
+    film_ior: f32,
+    substrate: Substrate,
+}
+
+impl ThinFilm {
+    // Thickness in nanometers, the film sits on smooth glass of index base_ior
+    pub fn dielectric(thickness: f32, film_ior: f32, base_ior: f32) -> ThinFilm {
+        ThinFilm::with_substrate(thickness, film_ior, Substrate::Dielectric(base_ior))
+    }
+
+    // Over smooth metal with the complex index eta + ik, given like Conductor's
+    pub fn conductor(thickness: f32, film_ior: f32, eta: Color, k: Color) -> ThinFilm {
+        ThinFilm::with_substrate(thickness, film_ior, Substrate::Conductor(eta, k))
+    }
+
+    fn with_substrate(thickness: f32, film_ior: f32, substrate: Substrate) -> ThinFilm {
+        ThinFilm {
+            thickness,
+            thickness_texture: Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))),
+            film_ior,
+            substrate,
+        }
+    }
+
+    // Scales the thickness by the average channel of the texture
+    pub fn with_thickness_texture(mut self, t: Arc<dyn Texture>) -> ThinFilm {
+        self.thickness_texture = t;
+        self
+    }
+
+    fn reflectance(&self, cos_i: f32, wavelength: f32, thickness: f32) -> f32 {
+        let (eta, k) = match self.substrate {
+            Substrate::Dielectric(ir) => (ir, 0.0),
+            Substrate::Conductor(eta, k) => (ThinFilm::at_wavelength(eta, wavelength), ThinFilm::at_wavelength(k, wavelength)),
+        };
+        microfacet::fresnel_thin_film(cos_i, wavelength, self.film_ior, thickness, eta, k)
+    }
+
+    fn thickness_at(&self, rec: &HitRecord) -> f32 {
+        let t = self.thickness_texture.value(rec.u, rec.v, rec.p);
+        self.thickness * (t.r() + t.g() + t.b()) / 3.0
+    }
 
-        let direction = if cannot_refract || will_reflect {
-            unit_direction.reflect(rec.normal)
+    // Film reflectance weighted by the color of each wavelength
+    fn average_reflectance(&self, cos_i: f32, thickness: f32) -> Color {
+        let sum = (0..THIN_FILM_SAMPLES).fold(Color::new(0.0, 0.0, 0.0), |sum, i| {
+            let wavelength = spectrum::sample_wavelength((i as f32 + 0.5) / THIN_FILM_SAMPLES as f32);
+            sum + self.reflectance(cos_i, wavelength, thickness) * spectrum::wavelength_to_rgb(wavelength)
+        });
+        sum / THIN_FILM_SAMPLES as f32
+    }
+
+    // Conductor indices are given at 650, 550 and 450 nm, linear in between
+    fn at_wavelength(c: Color, wavelength: f32) -> f32 {
+        let f = ((wavelength - 450.0) / 100.0).clamp(0.0, 2.0);
+        if f < 1.0 {
+            c.b() + f * (c.g() - c.b())
         } else {
-            unit_direction.refract(rec.normal, refraction_ratio)
+            c.g() + (f - 1.0) * (c.r() - c.g())
+        }
+    }
+}
+
+impl Scatter for ThinFilm {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let ir = match self.substrate {
+            // The film is only seen from outside, inside the glass is bare
+            Substrate::Dielectric(ir) if !rec.front_face => {
+                let (weight, direction) = scatter_dielectric(ir, 0.0, r_in, rec)?;
+                return Some((Color::new(weight, weight, weight), Ray::new(rec.p, direction, r_in.time())));
+            }
+            Substrate::Dielectric(ir) => Some(ir),
+            Substrate::Conductor(..) => None,
         };
 
-        let scattered = Ray::new(
-            rec.p,
-            direction + self.fuzz * Vec3::random_in_hemisphere(rec.normal),
-            r_in.time(),
-        );
+        let frame = Onb::build_from_w(rec.normal);
+        let wo = frame.world_to_local((-1.0) * r_in.direction().unit_vector());
+        let wo = Vec3::new(wo.x(), wo.y(), wo.z().max(1e-4)).unit_vector();
+        let mirrored = Ray::new(rec.p, frame.local(Vec3::new(-wo.x(), -wo.y(), wo.z())), r_in.time());
 
-        Some((self.albedo, scattered))
+        let thickness = self.thickness_at(rec);
+        let reflectance = match r_in.wavelength() {
+            Some(wavelength) => {
+                let r = self.reflectance(wo.z(), wavelength, thickness);
+                Color::new(r, r, r)
+            }
+            None => self.average_reflectance(wo.z(), thickness),
+        };
+
+        // Whatever the film does not reflect the metal absorbs
+        let Some(ir) = ir else {
+            return Some((reflectance, mirrored));
+        };
+
+        // Glass reflects with the probability of the average reflectance
+        let p_reflect = ((reflectance.r() + reflectance.g() + reflectance.b()) / 3.0).clamp(1e-4, 1.0 - 1e-4);
+        if rand::thread_rng().gen::<f32>() < p_reflect {
+            return Some((reflectance / p_reflect, mirrored));
+        }
+
+        // A film this thin bends nothing, the light goes straight into the glass
+        let wi = microfacet::refract(wo, Vec3::new(0.0, 0.0, 1.0), ir)?;
+        let transmittance = (Color::new(1.0, 1.0, 1.0) - reflectance) / (1.0 - p_reflect);
+        Some((transmittance, Ray::new(rec.p, frame.local(wi), r_in.time())))
     }
 }
@@ -0,0 +1,282 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::mat::{Isotropic, Scatter};
+use super::microfacet;
+use super::onb::Onb;
+use super::ray::Ray;
+use super::vec::{Color, Vec3};
+use rand::Rng;
+use std::sync::Arc;
+
+pub struct ConstantMedium {
+    boundary: Box<dyn Hit>,
+    neg_inv_density: f32,
+    phase_function: Arc<dyn Scatter>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hit>, density: f32, albedo: Color) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Arc::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        // The boundary has to be convex: the ray enters at the first hit and leaves at the second.
+        let rec1 = self.boundary.hit(r, f32::NEG_INFINITY, f32::INFINITY)?;
+        let rec2 = self.boundary.hit(r, rec1.t + 0.0001, f32::INFINITY)?;
+
+        let t1 = rec1.t.max(t_min).max(0.0);
+        let t2 = rec2.t.min(t_max);
+        if t1 >= t2 {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t2 - t1) * ray_length;
+        let hit_distance = self.neg_inv_density * rand::thread_rng().gen::<f32>().ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t1 + hit_distance / ray_length;
+
+        // Normal and front_face are meaningless inside a volume, the phase function ignores them.
+        let mut rec = HitRecord::new(r, t, Vec3::new(1.0, 0.0, 0.0), self.phase_function.clone());
+        rec.front_face = true;
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+// Samples a direction around w following the Henyey-Greenstein phase function,
+// g > 0 scatters forward, g < 0 backwards and g = 0 is isotropic.
+pub fn sample_henyey_greenstein(w: Vec3, g: f32) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let xi1: f32 = rng.gen();
+    let xi2: f32 = rng.gen();
+
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi1
+    } else {
+        let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi1);
+        (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+    }
+    .clamp(-1.0, 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * xi2;
+
+    Onb::build_from_w(w).local(Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+}
+
+// Homogeneous medium filling the whole scene
+#[derive(Clone, Copy)]
+pub struct Atmosphere {
+    density: f32,
+    albedo: Color,
+    g: f32,
+    extent: f32,
+}
+
+impl Atmosphere {
+    // extent is how much fog a ray crosses before it escapes to the background
+    pub fn new(density: f32, albedo: Color, g: f32, extent: f32) -> Atmosphere {
+        Atmosphere {
+            density,
+            albedo,
+            g,
+            extent,
+        }
+    }
+
+    // Samples a scattering event on the segment from the origin of r to t_hit,
+    // returns the attenuation and the scattered ray.
+    pub fn scatter(&self, r: &Ray, t_hit: Option<f32>) -> Option<(Color, Ray)> {
+        if self.density <= 0.0 {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let segment = match t_hit {
+            Some(t) => t * ray_length,
+            None => self.extent,
+        };
+        let distance = -(1.0 - rand::thread_rng().gen::<f32>()).ln() / self.density;
+        if distance >= segment {
+            return None;
+        }
+
+        let scattered = Ray::new(
+            r.at(distance / ray_length),
+            sample_henyey_greenstein(r.direction(), self.g),
+            r.time(),
+        );
+
+        Some((self.albedo, scattered))
+    }
+}
+
+// Scattering events a walk inside a Subsurface object takes before Russian
+// roulette may end it
+const ROULETTE_START: usize = 64;
+
+// Translucent solid like skin, wax or marble. Light refracts in through the
+// smooth boundary, which has to be closed, and random walks inside from one
+// scattering event to the next until it refracts out again, possibly far from
+// where it came in. The whole walk happens in one scatter so it doesn't eat
+// into the path depth.
+pub struct Subsurface {
+    walk: Arc<RandomWalk>,
+}
+
+impl Subsurface {
+    // mean_free_path is the average distance between scattering events and
+    // albedo what each of them keeps
+    pub fn new(boundary: Box<dyn Hit>, index_of_refraction: f32, mean_free_path: f32, albedo: Color) -> Subsurface {
+        Subsurface {
+            walk: Arc::new(RandomWalk {
+                boundary: Arc::from(boundary),
+                ir: index_of_refraction,
+                mean_free_path: Color::new(mean_free_path, mean_free_path, mean_free_path),
+                albedo,
+                g: 0.0,
+            }),
+        }
+    }
+
+    // A mean free path per channel, light that goes deeper bleeds further
+    // like the red under skin
+    pub fn with_mean_free_path(mut self, mean_free_path: Color) -> Subsurface {
+        let mut walk = (*self.walk).clone();
+        walk.mean_free_path = mean_free_path;
+        self.walk = Arc::new(walk);
+        self
+    }
+
+    // Henyey-Greenstein asymmetry of the scattering inside, like skin's forward scattering
+    pub fn with_anisotropy(mut self, g: f32) -> Subsurface {
+        let mut walk = (*self.walk).clone();
+        walk.g = g;
+        self.walk = Arc::new(walk);
+        self
+    }
+}
+
+impl Hit for Subsurface {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mut rec = self.walk.boundary.hit(r, t_min, t_max)?;
+        rec.mat = self.walk.clone();
+        // The walk mostly leaves from another point, the entry point's leak
+        // check doesn't apply to it
+        rec.geometric_normal = rec.normal;
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.walk.boundary.bounding_box(time0, time1)
+    }
+}
+
+#[derive(Clone)]
+struct RandomWalk {
+    boundary: Arc<dyn Hit>,
+    ir: f32,
+    mean_free_path: Color,
+    albedo: Color,
+    g: f32,
+}
+
+impl RandomWalk {
+    // Mirror or refraction at the smooth boundary, picked by its Fresnel term.
+    // eta is the index past the boundary over the one on the side of w.
+    fn cross(w: Vec3, normal: Vec3, eta: f32) -> (Vec3, bool) {
+        let cos_i = (-w.unit_vector().dot(normal)).clamp(0.0, 1.0);
+        let refracted = microfacet::refract((-1.0) * w.unit_vector(), normal, eta);
+        match refracted {
+            Some(d) if rand::thread_rng().gen::<f32>() >= microfacet::fresnel_dielectric(cos_i, eta) => (d, true),
+            _ => (w.reflect(normal), false),
+        }
+    }
+}
+
+impl Scatter for RandomWalk {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut rng = rand::thread_rng();
+        let mut direction = r_in.direction().unit_vector();
+
+        // Hit from outside, the light may just bounce off the surface
+        if rec.front_face {
+            let (d, inside) = RandomWalk::cross(direction, rec.normal, self.ir);
+            if !inside {
+                return Some((Color::new(1.0, 1.0, 1.0), Ray::new(rec.p, d, r_in.time())));
+            }
+            direction = d;
+        }
+
+        // The whole walk follows the extinction of one channel picked at
+        // random. Weighting it by the average of what each channel's walk
+        // would have had as probability keeps the colors from firefly noise.
+        // Only the ratios between channels matter, so those probabilities are
+        // kept relative to the largest.
+        let mfp = self.mean_free_path;
+        let sigma_t = Color::new(1.0 / mfp.r().max(1e-6), 1.0 / mfp.g().max(1e-6), 1.0 / mfp.b().max(1e-6));
+        let transmittance = |d: f32| Color::new((-sigma_t.r() * d).exp(), (-sigma_t.g() * d).exp(), (-sigma_t.b() * d).exp());
+        let channel = rng.gen_range(0..3);
+        let mut pdf = Color::new(1.0, 1.0, 1.0);
+
+        let mut p = rec.p;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        for events in 0.. {
+            let ray = Ray::new(p, direction, r_in.time());
+            let exit = self.boundary.hit(&ray, 0.0001, f32::INFINITY)?;
+            let distance = -(1.0 - rng.gen::<f32>()).ln() / sigma_t[channel];
+
+            if distance < exit.t {
+                pdf *= sigma_t * transmittance(distance);
+                p = ray.at(distance);
+                direction = sample_henyey_greenstein(direction, self.g).unit_vector();
+
+                // Long walks end at random instead of at a fixed count: each
+                // event survives with what the albedo keeps at most, and the
+                // survivors make up for the others
+                if events >= ROULETTE_START {
+                    let survival = self.albedo.r().max(self.albedo.g()).max(self.albedo.b()).min(1.0);
+                    if rng.gen::<f32>() >= survival {
+                        return None;
+                    }
+                    throughput *= self.albedo / survival;
+                } else {
+                    throughput *= self.albedo;
+                }
+            } else {
+                pdf *= transmittance(exit.t);
+                // At the boundary from inside, out or back in
+                let (d, out) = RandomWalk::cross(direction, exit.normal, 1.0 / self.ir);
+                p = exit.p;
+                direction = d;
+                if out {
+                    let average = (pdf.r() + pdf.g() + pdf.b()) / 3.0;
+                    if average <= 0.0 {
+                        return None;
+                    }
+                    return Some((throughput * pdf / average, Ray::new(p, direction, r_in.time())));
+                }
+            }
+
+            let largest = pdf.r().max(pdf.g()).max(pdf.b());
+            if largest <= 0.0 {
+                return None;
+            }
+            pdf /= largest;
+        }
+
+        None
+    }
+}
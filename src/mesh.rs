@@ -0,0 +1,228 @@
+use super::aabb::Aabb;
+use super::bvh::Bvh;
+use super::hit::{Hit, HitRecord, World};
+use super::mat::Scatter;
+use super::ray::Ray;
+use super::transform::Transform;
+use super::vec::{Color, Point3, Vec3};
+use super::{ply, stl};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// Indexed triangles. Normals, colors and uvs are per vertex and empty when
+// the source has none.
+#[derive(Default)]
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub colors: Vec<Color>,
+    pub uvs: Vec<(f32, f32)>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    // PLY or STL, picked by the file extension
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Mesh> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let mesh = match extension.as_deref() {
+            Some("ply") => ply::load(path)?,
+            Some("stl") => stl::load(path)?,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "only .ply and .stl meshes are supported",
+                ))
+            }
+        };
+
+        if mesh.triangles.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "mesh has no triangles"));
+        }
+        Ok(mesh)
+    }
+
+    // Scales uniformly so the largest side of the bounds is size and moves the
+    // center of the bottom face to base
+    pub fn fit(&mut self, base: Point3, size: f32) {
+        let (low, high) = self.positions.iter().fold(
+            (Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY), Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)),
+            |(mut low, mut high), p| {
+                for a in 0..3 {
+                    low[a] = low[a].min(p[a]);
+                    high[a] = high[a].max(p[a]);
+                }
+                (low, high)
+            },
+        );
+        let extent = high - low;
+        let largest = extent.x().max(extent.y()).max(extent.z());
+        if largest <= 0.0 {
+            return;
+        }
+
+        let scale = size / largest;
+        let bottom = Point3::new(0.5 * (low.x() + high.x()), low.y(), 0.5 * (low.z() + high.z()));
+        for p in &mut self.positions {
+            *p = base + scale * (*p - bottom);
+        }
+    }
+
+    pub fn transform(&mut self, t: &Transform) {
+        for p in &mut self.positions {
+            *p = t.point(*p);
+        }
+        for n in &mut self.normals {
+            *n = t.normal(*n);
+        }
+    }
+}
+
+// Moller-Trumbore, returns the root and the barycentric coordinates of b and c
+pub fn hit_triangle(r: &Ray, a: Point3, b: Point3, c: Point3) -> Option<(f32, f32, f32)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let pvec = r.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = r.origin() - a;
+    let u = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let v = r.direction().dot(qvec) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    Some((edge2.dot(qvec) * inv_det, u, v))
+}
+
+struct Triangle {
+    mesh: Arc<Mesh>,
+    index: usize,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Triangle {
+    // Derivatives along the texture coordinates, or the barycentric ones without uvs
+    fn derivatives(&self, a: Point3, b: Point3, c: Point3) -> (Vec3, Vec3) {
+        if !self.mesh.uvs.is_empty() {
+            let [uv_a, uv_b, uv_c] = self.mesh.triangles[self.index].map(|i| self.mesh.uvs[i]);
+            let (du02, dv02) = (uv_a.0 - uv_c.0, uv_a.1 - uv_c.1);
+            let (du12, dv12) = (uv_b.0 - uv_c.0, uv_b.1 - uv_c.1);
+            let det = du02 * dv12 - dv02 * du12;
+            if det.abs() > 1e-12 {
+                let (dp02, dp12) = (a - c, b - c);
+                let dpdu = (dv12 * dp02 - dv02 * dp12) / det;
+                let dpdv = (du02 * dp12 - du12 * dp02) / det;
+                return (dpdu, dpdv);
+            }
+        }
+        (b - a, c - a)
+    }
+
+    fn interpolate<T, F>(values: &[T], [a, b, c]: [usize; 3], u: f32, v: f32, f: F) -> Option<Vec3>
+    where
+        F: Fn(&T) -> Vec3,
+    {
+        if values.is_empty() {
+            return None;
+        }
+        Some((1.0 - u - v) * f(&values[a]) + u * f(&values[b]) + v * f(&values[c]))
+    }
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let vertices = self.mesh.triangles[self.index];
+        let [a, b, c] = vertices.map(|i| self.mesh.positions[i]);
+        let (t, u, v) = hit_triangle(r, a, b, c)?;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // Smooth shading when the mesh has vertex normals
+        let face_normal = (b - a).cross(c - a);
+        let outward_normal = Triangle::interpolate(&self.mesh.normals, vertices, u, v, |&n| n)
+            .filter(|n| !n.near_zero())
+            .unwrap_or(face_normal)
+            .unit_vector();
+
+        let mut rec = HitRecord::new(r, t, outward_normal, self.mat.clone());
+        rec.set_geometric_normal(face_normal.unit_vector());
+        match Triangle::interpolate(&self.mesh.uvs, vertices, u, v, |&(s, t)| Vec3::new(s, t, 0.0)) {
+            Some(uv) => {
+                rec.u = uv.x();
+                rec.v = uv.y();
+            }
+            None => {
+                rec.u = u;
+                rec.v = v;
+            }
+        }
+        rec.vertex_color = Triangle::interpolate(&self.mesh.colors, vertices, u, v, |&c| c);
+        (rec.dpdu, rec.dpdv) = self.derivatives(a, b, c);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let [a, b, c] = self.mesh.triangles[self.index].map(|i| self.mesh.positions[i]);
+        let bounds = Aabb::surrounding_box(Aabb::new(a, b), Aabb::new(c, c));
+        // Padding so triangles lying in an axis plane don't get a flat box
+        let pad = Vec3::new(1e-4, 1e-4, 1e-4);
+        Some(Aabb::new(bounds.min() - pad, bounds.max() + pad))
+    }
+}
+
+// Mesh triangles in a BVH, all with the same material
+pub struct TriangleMesh {
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: Mesh, m: Arc<dyn Scatter>) -> TriangleMesh {
+        let count = mesh.positions.len();
+        assert!(
+            mesh.triangles.iter().flatten().all(|&i| i < count),
+            "mesh triangle refers to a missing vertex"
+        );
+        for (name, len) in [("normals", mesh.normals.len()), ("colors", mesh.colors.len()), ("uvs", mesh.uvs.len())] {
+            assert!(len == 0 || len == count, "mesh {} do not match the vertex count", name);
+        }
+
+        let mesh = Arc::new(mesh);
+        let triangles: World = (0..mesh.triangles.len())
+            .map(|index| {
+                Box::new(Triangle {
+                    mesh: mesh.clone(),
+                    index,
+                    mat: m.clone(),
+                }) as Box<dyn Hit>
+            })
+            .collect();
+
+        TriangleMesh {
+            bvh: Bvh::new(triangles, 0.0, 1.0),
+        }
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.bvh.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.bvh.bounding_box(time0, time1)
+    }
+}
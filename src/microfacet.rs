@@ -0,0 +1,195 @@
+use super::vec::{Color, Vec3};
+use std::f32::consts::PI;
+use std::ops;
+
+// GGX (Trowbridge-Reitz) helpers. Directions are in the local shading frame
+// with the normal along +z, alpha is (alpha_x, alpha_y).
+
+// Smith auxiliary function
+pub fn lambda(w: Vec3, alpha: (f32, f32)) -> f32 {
+    let cos2 = w.z() * w.z();
+    if cos2 == 0.0 {
+        return f32::INFINITY;
+    }
+    let a2 = (alpha.0 * alpha.0 * w.x() * w.x() + alpha.1 * alpha.1 * w.y() * w.y()) / cos2;
+    0.5 * ((1.0 + a2).sqrt() - 1.0)
+}
+
+pub fn smith_g1(w: Vec3, alpha: (f32, f32)) -> f32 {
+    1.0 / (1.0 + lambda(w, alpha))
+}
+
+// Height correlated shadowing and masking
+pub fn smith_g2(wo: Vec3, wi: Vec3, alpha: (f32, f32)) -> f32 {
+    1.0 / (1.0 + lambda(wo, alpha) + lambda(wi, alpha))
+}
+
+// Microfacet normal from the distribution of normals visible from wo (Heitz 2018)
+pub fn sample_visible_normal(wo: Vec3, alpha: (f32, f32), u1: f32, u2: f32) -> Vec3 {
+    // Stretch to the hemisphere configuration
+    let vh = Vec3::new(alpha.0 * wo.x(), alpha.1 * wo.y(), wo.z()).unit_vector();
+
+    let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+    let t1 = if lensq > 0.0 {
+        Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = vh.cross(t1);
+
+    // Point on the projected half disk
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z());
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+    Vec3::new(alpha.0 * nh.x(), alpha.1 * nh.y(), nh.z().max(1e-6)).unit_vector()
+}
+
+pub fn schlick(f0: Color, cosine: f32) -> Color {
+    let m = (1.0 - cosine).clamp(0.0, 1.0).powi(5);
+    f0 + m * (Color::new(1.0, 1.0, 1.0) - f0)
+}
+
+// Exact Fresnel reflectance of a conductor with complex index eta + i k, per channel
+pub fn fresnel_conductor(cosine: f32, eta: Color, k: Color) -> Color {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = cosine.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + cos2;
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cosine * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Color::new(channel(eta.r(), k.r()), channel(eta.g(), k.g()), channel(eta.b(), k.b()))
+}
+
+// Fresnel reflectance of a dielectric interface, eta is the index on the
+// other side over the index on the incident side
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// Direction refracted through the microfacet normal h, None on total internal reflection
+pub fn refract(wo: Vec3, h: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = wo.dot(h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(((-1.0 / eta) * wo + (cos_i / eta - cos_t) * h).unit_vector())
+}
+
+// Just enough complex arithmetic for absorbing media
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal root
+    fn sqrt(self) -> Complex {
+        let r = self.norm_sqr().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+impl ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(self.re * rhs.re - self.im * rhs.im, self.re * rhs.im + self.im * rhs.re)
+    }
+}
+
+impl ops::Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Complex {
+        let d = rhs.norm_sqr();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+// Reflectance of a film of index film_ior and thickness in nanometers, between
+// the outside of index 1 and a substrate of complex index eta + i k, at the
+// given wavelength. The Airy sum of the waves bouncing inside the film.
+pub fn fresnel_thin_film(cos_i: f32, wavelength: f32, film_ior: f32, thickness: f32, eta: f32, k: f32) -> f32 {
+    let cos1 = cos_i.clamp(0.0, 1.0);
+    let sin2_1 = 1.0 - cos1 * cos1;
+    let cos2 = (1.0 - sin2_1 / (film_ior * film_ior)).max(0.0).sqrt();
+    let n3 = Complex::new(eta, k);
+    let cos3 = (Complex::new(1.0, 0.0) - Complex::new(sin2_1, 0.0) / (n3 * n3)).sqrt();
+
+    let real = |x: f32| Complex::new(x, 0.0);
+    let (n2, c1, c2) = (real(film_ior), real(cos1), real(cos2));
+    let r12 = [(c1 - n2 * c2) / (c1 + n2 * c2), (n2 * c1 - c2) / (n2 * c1 + c2)];
+    let r23 = [
+        (n2 * c2 - n3 * cos3) / (n2 * c2 + n3 * cos3),
+        (n3 * c2 - n2 * cos3) / (n3 * c2 + n2 * cos3),
+    ];
+
+    // Phase difference of one round trip through the film
+    let delta = 4.0 * PI * film_ior * thickness * cos2 / wavelength;
+    let phase = Complex::new(delta.cos(), delta.sin());
+    let reflectance = |r12: Complex, r23: Complex| ((r12 + r23 * phase) / (real(1.0) + r12 * r23 * phase)).norm_sqr();
+    0.5 * (reflectance(r12[0], r23[0]) + reflectance(r12[1], r23[1]))
+}
+
+pub fn sample_cosine_hemisphere(u1: f32, u2: f32) -> Vec3 {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt())
+}
+
+// Perceptual roughness to alpha, kept away from zero so the math stays finite
+pub fn roughness_to_alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(1e-3)
+}
@@ -0,0 +1,49 @@
+use super::vec::Vec3;
+
+// Orthonormal basis with w along a given direction
+#[derive(Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+
+        Onb { u, v, w }
+    }
+
+    // With u along the part of tangent perpendicular to n, any u when they are parallel
+    pub fn build_from_w_and_tangent(n: Vec3, tangent: Vec3) -> Onb {
+        let w = n.unit_vector();
+        let u = tangent - tangent.dot(w) * w;
+        if u.near_zero() {
+            return Onb::build_from_w(n);
+        }
+        let u = u.unit_vector();
+        Onb { u, v: w.cross(u), w }
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    // From local coordinates to world
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    // From world coordinates to local
+    pub fn world_to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -0,0 +1,534 @@
+use super::camera::Camera;
+use super::hit::World;
+use super::mat::{Conductor, Dielectric, DiffuseLight, Lambertian, MixMaterial, OrenNayar, Scatter};
+use super::mesh::{Mesh, TriangleMesh};
+use super::sphere::Sphere;
+use super::transform::Transform;
+use super::vec::{Color, Point3, Vec3};
+use super::volume::blackbody;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[derive(Clone)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f32),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> io::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ']' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => s.push(c),
+                        None => return Err(invalid_data("unterminated string in pbrt file".to_string())),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '[' | ']' | '"' | '#') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if word.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.')) {
+                    let n = word
+                        .parse()
+                        .map_err(|_| invalid_data(format!("bad number {} in pbrt file", word)))?;
+                    tokens.push(Token::Num(n));
+                } else {
+                    tokens.push(Token::Ident(word));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Parameter like "float fov" [ 45 ]
+struct Param {
+    ty: String,
+    name: String,
+    values: Vec<Token>,
+}
+
+struct Params(Vec<Param>);
+
+impl Params {
+    fn parse(tokens: &[Token]) -> io::Result<Params> {
+        let mut params = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let declaration = match &tokens[i] {
+                Token::Str(s) => s,
+                _ => return Err(invalid_data("expected a parameter declaration in pbrt file".to_string())),
+            };
+            let mut words = declaration.split_whitespace();
+            let (ty, name) = match (words.next(), words.next()) {
+                (Some(ty), Some(name)) => (ty.to_string(), name.to_string()),
+                _ => return Err(invalid_data(format!("bad parameter declaration \"{}\"", declaration))),
+            };
+
+            i += 1;
+            let mut values = Vec::new();
+            match tokens.get(i) {
+                Some(Token::Open) => {
+                    i += 1;
+                    while let Some(token) = tokens.get(i) {
+                        i += 1;
+                        if let Token::Close = token {
+                            break;
+                        }
+                        values.push(token.clone());
+                    }
+                }
+                Some(token) => {
+                    values.push(token.clone());
+                    i += 1;
+                }
+                None => return Err(invalid_data(format!("parameter {} has no value", name))),
+            }
+            params.push(Param { ty, name, values });
+        }
+        Ok(Params(params))
+    }
+
+    fn get(&self, name: &str) -> Option<&Param> {
+        self.0.iter().find(|p| p.name == name)
+    }
+
+    fn floats(&self, name: &str) -> Option<Vec<f32>> {
+        self.get(name).map(|p| {
+            p.values
+                .iter()
+                .filter_map(|v| match v {
+                    Token::Num(n) => Some(*n),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
+    fn float(&self, name: &str, default: f32) -> f32 {
+        self.floats(name).and_then(|f| f.first().copied()).unwrap_or(default)
+    }
+
+    fn string(&self, name: &str) -> Option<String> {
+        self.get(name).and_then(|p| match p.values.first() {
+            Some(Token::Str(s)) => Some(s.clone()),
+            _ => None,
+        })
+    }
+
+    fn bool(&self, name: &str, default: bool) -> bool {
+        match self.get(name).and_then(|p| p.values.first()) {
+            Some(Token::Str(s)) | Some(Token::Ident(s)) => s == "true",
+            _ => default,
+        }
+    }
+
+    // rgb values or a blackbody temperature and scale, other spectra are not supported
+    fn color(&self, name: &str) -> Option<Color> {
+        let param = self.get(name)?;
+        let f = self.floats(name)?;
+        match (param.ty.as_str(), f.as_slice()) {
+            ("rgb" | "color", [r, g, b, ..]) => Some(Color::new(*r, *g, *b)),
+            ("blackbody", [kelvin, scale, ..]) => Some(*scale * blackbody(*kelvin)),
+            ("blackbody", [kelvin]) => Some(blackbody(*kelvin)),
+            _ => None,
+        }
+    }
+}
+
+// pbrt's mapping from user roughness to the GGX alpha
+fn roughness_to_alpha(roughness: f32) -> f32 {
+    let x = roughness.max(1e-3).ln();
+    1.62142 + 0.819955 * x + 0.1734 * x * x + 0.0171201 * x * x * x + 0.000640711 * x * x * x * x
+}
+
+fn make_material(ty: &str, params: &Params, named: &HashMap<String, Arc<dyn Scatter>>) -> io::Result<Arc<dyn Scatter>> {
+    let material: Arc<dyn Scatter> = match ty {
+        "metal" => {
+            let to_roughness = |roughness: f32| {
+                let alpha = if params.bool("remaproughness", true) {
+                    roughness_to_alpha(roughness)
+                } else {
+                    roughness
+                };
+                alpha.max(0.0).sqrt()
+            };
+            let roughness = params.float("roughness", 0.01);
+            let along_u = to_roughness(params.float("uroughness", roughness));
+            let along_v = to_roughness(params.float("vroughness", roughness));
+
+            // Named spectra of the metals we have presets for, rgb otherwise and copper by default
+            let metal = match params.string("eta").as_deref() {
+                Some("metal-Au-eta") => Conductor::gold(along_u),
+                Some("metal-Cu-eta") => Conductor::copper(along_u),
+                Some("metal-Al-eta") => Conductor::aluminum(along_u),
+                Some("metal-Ag-eta") => Conductor::silver(along_u),
+                _ => match (params.color("eta"), params.color("k")) {
+                    (Some(eta), Some(k)) => Conductor::new(eta, k, along_u),
+                    _ => Conductor::copper(along_u),
+                },
+            };
+            Arc::new(metal.with_anisotropic_roughness(along_u, along_v))
+        }
+        "glass" => {
+            let eta = params.float("index", params.float("eta", 1.5));
+            let roughness = params.float("roughness", params.float("uroughness", 0.0));
+            let alpha = if roughness > 0.0 && params.bool("remaproughness", true) {
+                roughness_to_alpha(roughness)
+            } else {
+                roughness
+            };
+            Arc::new(Dielectric::new(eta, alpha.max(0.0).sqrt()))
+        }
+        // Like pbrt-v3, amount is the weight of the first material
+        "mix" => {
+            let lookup = |key: &str| {
+                let name = params.string(key).unwrap_or_default();
+                named
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| invalid_data(format!("unknown material {}", name)))
+            };
+            let amount = match params.color("amount") {
+                Some(c) => (c.r() + c.g() + c.b()) / 3.0,
+                None => params.float("amount", 0.5),
+            };
+            Arc::new(MixMaterial::new(lookup("namedmaterial1")?, lookup("namedmaterial2")?, 1.0 - amount))
+        }
+        // matte and anything else become diffuse with their Kd, rough when matte has a sigma
+        _ => {
+            let kd = params.color("Kd").unwrap_or(Color::new(0.5, 0.5, 0.5));
+            let sigma = if ty == "matte" { params.float("sigma", 0.0) } else { 0.0 };
+            if sigma > 0.0 {
+                Arc::new(OrenNayar::new(kd, sigma))
+            } else {
+                Arc::new(Lambertian::new(kd))
+            }
+        }
+    };
+    Ok(material)
+}
+
+#[derive(Clone)]
+struct State {
+    ctm: Transform,
+    material: Arc<dyn Scatter>,
+    // Emitted radiance and whether both sides emit
+    area_light: Option<(Color, bool)>,
+}
+
+pub struct PbrtScene {
+    pub world: World,
+    pub camera: Option<Camera>,
+    pub resolution: (u32, u32),
+}
+
+struct Parser {
+    base: PathBuf,
+    state: State,
+    attributes: Vec<State>,
+    transforms: Vec<Transform>,
+    named_materials: HashMap<String, Arc<dyn Scatter>>,
+    camera: Option<(Transform, Params)>,
+    resolution: (u32, u32),
+    world: World,
+}
+
+impl Parser {
+    fn shape(&mut self, ty: &str, params: &Params) -> io::Result<()> {
+        let mat: Arc<dyn Scatter> = match self.state.area_light {
+            Some((emit, two_sided)) => Arc::new(DiffuseLight::new(emit, two_sided)),
+            None => self.state.material.clone(),
+        };
+        let ctm = self.state.ctm;
+
+        let mut mesh = match ty {
+            "sphere" => {
+                // Uniform scales only, the radius follows the length of the x axis
+                let center = ctm.point(Point3::new(0.0, 0.0, 0.0));
+                let radius = params.float("radius", 1.0) * ctm.vector(Vec3::new(1.0, 0.0, 0.0)).length();
+                self.world.push(Box::new(Sphere::new(center, radius, mat)));
+                return Ok(());
+            }
+            "trianglemesh" => {
+                let points = params.floats("P").unwrap_or_default();
+                let indices: Vec<usize> = match params.floats("indices") {
+                    Some(indices) => indices.iter().map(|&i| i as usize).collect(),
+                    None if points.len() == 9 => vec![0, 1, 2],
+                    None => return Err(invalid_data("trianglemesh without indices".to_string())),
+                };
+                let triplets = |f: Vec<f32>| f.chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect();
+                let uvs = params
+                    .floats("uv")
+                    .or_else(|| params.floats("st"))
+                    .map(|f| f.chunks_exact(2).map(|c| (c[0], c[1])).collect())
+                    .unwrap_or_default();
+
+                let mesh = Mesh {
+                    positions: triplets(points),
+                    normals: params.floats("N").map(triplets).unwrap_or_default(),
+                    uvs,
+                    triangles: indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+                    ..Mesh::default()
+                };
+                let count = mesh.positions.len();
+                if mesh.triangles.iter().flatten().any(|&i| i >= count) {
+                    return Err(invalid_data("trianglemesh index refers to a missing point".to_string()));
+                }
+                if [mesh.normals.len(), mesh.uvs.len()].iter().any(|&n| n != 0 && n != count) {
+                    return Err(invalid_data("trianglemesh N or uv count does not match P".to_string()));
+                }
+                mesh
+            }
+            "plymesh" => {
+                let filename = params
+                    .string("filename")
+                    .ok_or_else(|| invalid_data("plymesh without a filename".to_string()))?;
+                Mesh::load(self.base.join(filename))?
+            }
+            _ => return Ok(()),
+        };
+
+        if mesh.triangles.is_empty() {
+            return Ok(());
+        }
+        mesh.transform(&ctm);
+        self.world.push(Box::new(TriangleMesh::new(mesh, mat)));
+        Ok(())
+    }
+
+    fn parse(&mut self, tokens: &[Token]) -> io::Result<()> {
+        let mut i = 0;
+        while i < tokens.len() {
+            let directive = match &tokens[i] {
+                Token::Ident(name) => name.as_str(),
+                _ => return Err(invalid_data("expected a directive in pbrt file".to_string())),
+            };
+            // Everything up to the next directive
+            let start = i + 1;
+            i = start;
+            while i < tokens.len() && !matches!(tokens[i], Token::Ident(_)) {
+                i += 1;
+            }
+            let args = &tokens[start..i];
+
+            let numbers: Vec<f32> = args
+                .iter()
+                .filter_map(|t| match t {
+                    Token::Num(n) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            let numbers_needed = |n: usize| {
+                if numbers.len() < n {
+                    Err(invalid_data(format!("{} needs {} numbers", directive, n)))
+                } else {
+                    Ok(&numbers[..n])
+                }
+            };
+            let vec3 = |f: &[f32]| Vec3::new(f[0], f[1], f[2]);
+            let matrix = |f: &[f32]| {
+                let mut columns = [[0.0; 4]; 4];
+                for (j, column) in columns.iter_mut().enumerate() {
+                    column.copy_from_slice(&f[4 * j..4 * j + 4]);
+                }
+                Transform::from_columns(columns)
+            };
+            // A type name followed by parameters
+            let typed = || -> io::Result<(String, Params)> {
+                match args.first() {
+                    Some(Token::Str(ty)) => Ok((ty.clone(), Params::parse(&args[1..])?)),
+                    _ => Err(invalid_data(format!("{} needs a type", directive))),
+                }
+            };
+
+            let ctm = self.state.ctm;
+            match directive {
+                "Identity" => self.state.ctm = Transform::identity(),
+                "Translate" => self.state.ctm = ctm.compose(&Transform::translate(vec3(numbers_needed(3)?))),
+                "Scale" => self.state.ctm = ctm.compose(&Transform::scale(vec3(numbers_needed(3)?))),
+                "Rotate" => {
+                    let f = numbers_needed(4)?;
+                    self.state.ctm = ctm.compose(&Transform::rotate(f[0], vec3(&f[1..])));
+                }
+                "LookAt" => {
+                    let f = numbers_needed(9)?;
+                    self.state.ctm = ctm.compose(&Transform::look_at(vec3(f), vec3(&f[3..]), vec3(&f[6..])));
+                }
+                "Transform" => self.state.ctm = matrix(numbers_needed(16)?),
+                "ConcatTransform" => self.state.ctm = ctm.compose(&matrix(numbers_needed(16)?)),
+                "Camera" => {
+                    let (ty, params) = typed()?;
+                    if ty != "perspective" {
+                        return Err(invalid_data(format!("{} cameras are not supported", ty)));
+                    }
+                    self.camera = Some((ctm, params));
+                }
+                "Film" => {
+                    let (_, params) = typed()?;
+                    // At least two pixels a side for the camera's uv
+                    self.resolution = (
+                        params.float("xresolution", 1280.0).max(2.0) as u32,
+                        params.float("yresolution", 720.0).max(2.0) as u32,
+                    );
+                }
+                "WorldBegin" => self.state.ctm = Transform::identity(),
+                "AttributeBegin" => self.attributes.push(self.state.clone()),
+                "AttributeEnd" => {
+                    self.state = self
+                        .attributes
+                        .pop()
+                        .ok_or_else(|| invalid_data("unmatched AttributeEnd".to_string()))?;
+                }
+                "TransformBegin" => self.transforms.push(ctm),
+                "TransformEnd" => {
+                    self.state.ctm = self
+                        .transforms
+                        .pop()
+                        .ok_or_else(|| invalid_data("unmatched TransformEnd".to_string()))?;
+                }
+                "Material" => {
+                    let (ty, params) = typed()?;
+                    self.state.material = make_material(&ty, &params, &self.named_materials)?;
+                }
+                "MakeNamedMaterial" => {
+                    let (name, params) = typed()?;
+                    let ty = params.string("type").unwrap_or_default();
+                    let material = make_material(&ty, &params, &self.named_materials)?;
+                    self.named_materials.insert(name, material);
+                }
+                "NamedMaterial" => {
+                    let (name, _) = typed()?;
+                    self.state.material = self
+                        .named_materials
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| invalid_data(format!("unknown material {}", name)))?;
+                }
+                "AreaLightSource" => {
+                    let (_, params) = typed()?;
+                    let emit = params.color("L").unwrap_or(Color::new(1.0, 1.0, 1.0));
+                    self.state.area_light = Some((emit, params.bool("twosided", false)));
+                }
+                "Shape" => {
+                    let (ty, params) = typed()?;
+                    self.shape(&ty, &params)?;
+                }
+                "Include" => {
+                    let (filename, _) = typed()?;
+                    let text = fs::read_to_string(self.base.join(filename))?;
+                    self.parse(&tokenize(&text)?)?;
+                }
+                // Samplers, integrators, lights other than area lights, textures,
+                // media and object instancing are not supported
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// Scene of a pbrt-v3 file. The camera needs a LookAt or another transform
+// before it like pbrt does, and its image is mirrored to match pbrt's left
+// handed coordinates.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PbrtScene> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let mut parser = Parser {
+        base: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        state: State {
+            ctm: Transform::identity(),
+            material: Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            area_light: None,
+        },
+        attributes: Vec::new(),
+        transforms: Vec::new(),
+        named_materials: HashMap::new(),
+        camera: None,
+        resolution: (1280, 720),
+        world: World::new(),
+    };
+    parser.parse(&tokenize(&text)?)?;
+
+    let (width, height) = parser.resolution;
+    let aspect_ratio = width as f32 / height as f32;
+    let camera = parser.camera.map(|(world_to_camera, params)| {
+        let camera_to_world = world_to_camera.inverse();
+        // fov is the angle of the shorter side of the image
+        let fov = params.float("fov", 90.0);
+        let vfov = if aspect_ratio >= 1.0 {
+            fov
+        } else {
+            2.0 * ((fov.to_radians() / 2.0).tan() / aspect_ratio).atan().to_degrees()
+        };
+
+        // Ray directions are as long as the focus distance, keep it short for pinholes
+        let lens_radius = params.float("lensradius", 0.0);
+        let focus_distance = if lens_radius > 0.0 {
+            params.float("focaldistance", 1e6)
+        } else {
+            1.0
+        };
+
+        Camera::new(
+            camera_to_world.point(Point3::new(0.0, 0.0, 0.0)),
+            camera_to_world.point(Point3::new(0.0, 0.0, 1.0)),
+            camera_to_world.vector(Vec3::new(0.0, 1.0, 0.0)),
+            vfov,
+            aspect_ratio,
+            2.0 * lens_radius,
+            focus_distance,
+            0.0,
+            1.0,
+        )
+        .flip_horizontal()
+    });
+
+    Ok(PbrtScene {
+        world: parser.world,
+        camera,
+        resolution: parser.resolution,
+    })
+}
@@ -0,0 +1,283 @@
+use super::mesh::Mesh;
+use super::vec::Vec3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> io::Result<Scalar> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(invalid_data("unknown ply property type")),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    // Count type and item type
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Values of the body, either whitespace separated text or packed binary
+struct Body<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: Format,
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> io::Result<f64> {
+        if self.format == Format::Ascii {
+            while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            if start == self.pos {
+                return Err(invalid_data("unexpected end of ply data"));
+            }
+            return String::from_utf8_lossy(&self.data[start..self.pos])
+                .parse::<f64>()
+                .map_err(|_| invalid_data("bad number in ply data"));
+        }
+
+        let size = scalar.size();
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| invalid_data("unexpected end of ply data"))?;
+        self.pos += size;
+
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            b[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        })
+    }
+
+    // Value of a scalar property, lists are skipped
+    fn property(&mut self, property: &Property) -> io::Result<f64> {
+        match property {
+            Property::Scalar(_, scalar) => self.read(*scalar),
+            Property::List(_, count, item) => {
+                for _ in 0..self.read(*count)? as usize {
+                    self.read(*item)?;
+                }
+                Ok(0.0)
+            }
+        }
+    }
+}
+
+fn parse_header(header: &str) -> io::Result<(Format, Vec<Element>)> {
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid_data("not a ply file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid_data("bad ply element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_data("ply property outside of an element"))?
+                .properties
+                .push(Property::List(name.to_string(), Scalar::parse(count)?, Scalar::parse(item)?)),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_data("ply property outside of an element"))?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(scalar)?)),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid_data("unexpected line in ply header")),
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid_data("ply header has no format"))?;
+    Ok((format, elements))
+}
+
+// Triangles and polygons, which are split in fans. Vertices can have normals
+// (nx, ny, nz), colors (red, green, blue) and uvs (u, v or s, t).
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Mesh> {
+    let data = fs::read(path)?;
+    let marker = b"end_header";
+    let end = data
+        .windows(marker.len())
+        .position(|w| w == marker)
+        .ok_or_else(|| invalid_data("ply header has no end"))?;
+    let body_start = data[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| end + i + 1)
+        .ok_or_else(|| invalid_data("ply header has no end"))?;
+    let (format, elements) = parse_header(&String::from_utf8_lossy(&data[..end]))?;
+
+    let mut body = Body {
+        data: &data[body_start..],
+        pos: 0,
+        format,
+    };
+    let mut mesh = Mesh::default();
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| matches!(p, Property::Scalar(n, _) if names.contains(&n.as_str())))
+        };
+
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+                let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+                let color = [
+                    find(&["red", "r", "diffuse_red"]),
+                    find(&["green", "g", "diffuse_green"]),
+                    find(&["blue", "b", "diffuse_blue"]),
+                ];
+                let uv = [find(&["u", "s", "texture_u", "texture_s"]), find(&["v", "t", "texture_v", "texture_t"])];
+                if position.iter().any(Option::is_none) {
+                    return Err(invalid_data("ply vertices need x, y and z"));
+                }
+
+                // Integer colors go from 0 to the largest value of their type
+                let color_scale = match element.properties.get(color[0].unwrap_or(usize::MAX)) {
+                    Some(Property::Scalar(_, scalar)) if !scalar.is_float() => {
+                        1.0 / (2f64.powi(8 * scalar.size() as i32) - 1.0)
+                    }
+                    _ => 1.0,
+                };
+
+                let mut values = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        *value = body.property(property)?;
+                    }
+
+                    let get = |i: [Option<usize>; 3], scale: f64| {
+                        let [x, y, z] = i.map(|i| (values[i.unwrap()] * scale) as f32);
+                        Vec3::new(x, y, z)
+                    };
+                    mesh.positions.push(get(position, 1.0));
+                    if normal.iter().all(Option::is_some) {
+                        mesh.normals.push(get(normal, 1.0));
+                    }
+                    if color.iter().all(Option::is_some) {
+                        mesh.colors.push(get(color, color_scale));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        mesh.uvs.push((values[u] as f32, values[v] as f32));
+                    }
+                }
+            }
+            "face" => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        match property {
+                            Property::List(name, count, item) if name == "vertex_indices" || name == "vertex_index" => {
+                                let n = body.read(*count)? as usize;
+                                // Not preallocated, the count may be corrupt
+                                let mut polygon = Vec::new();
+                                for _ in 0..n {
+                                    polygon.push(body.read(*item)? as usize);
+                                }
+                                for i in 1..n.saturating_sub(1) {
+                                    mesh.triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+                                }
+                            }
+                            _ => {
+                                body.property(property)?;
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        body.property(property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let count = mesh.positions.len();
+    if mesh.triangles.iter().flatten().any(|&i| i >= count) {
+        return Err(invalid_data("ply face refers to a missing vertex"));
+    }
+    Ok(mesh)
+}
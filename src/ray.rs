@@ -4,6 +4,8 @@ pub struct Ray {
     orig: Point3,
     dir: Vec3,
     tm: f32,
+    // In nanometers, set once the path goes through something wavelength dependent
+    wavelength: Option<f32>,
 }
 
 impl Ray {
@@ -12,6 +14,20 @@ impl Ray {
             orig: origin,
             dir: direction,
             tm: time,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f32>) -> Ray {
+        self.wavelength = wavelength;
+        self
+    }
+
+    // Scattered rays keep the wavelength of the path they continue
+    pub fn inherit_wavelength(self, from: &Ray) -> Ray {
+        match self.wavelength {
+            Some(_) => self,
+            None => self.with_wavelength(from.wavelength),
         }
     }
 
@@ -30,4 +46,8 @@ impl Ray {
     pub fn time(&self) -> f32 {
         self.tm
     }
+
+    pub fn wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
 }
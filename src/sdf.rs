@@ -0,0 +1,241 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::mat::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::sync::Arc;
+
+// Signed distance field built as a tree of primitives and operators
+pub enum Sdf {
+    Sphere(f32),
+    // Box with the given half extents and rounded edges
+    RoundBox(Vec3, f32),
+    // Torus around the Y axis
+    Torus(f32, f32),
+    Translate(Vec3, Box<Sdf>),
+    Union(Box<Sdf>, Box<Sdf>),
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f32),
+    Subtract(Box<Sdf>, Box<Sdf>),
+    // Copies every period along each axis, count times to each side
+    Repeat(Vec3, [i32; 3], Box<Sdf>),
+    // Rotates around Y by the given radians per unit of height
+    Twist(f32, Box<Sdf>),
+}
+
+impl Sdf {
+    pub fn sphere(radius: f32) -> Sdf {
+        Sdf::Sphere(radius)
+    }
+
+    pub fn round_box(half_extents: Vec3, radius: f32) -> Sdf {
+        Sdf::RoundBox(half_extents, radius)
+    }
+
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Sdf {
+        Sdf::Torus(major_radius, minor_radius)
+    }
+
+    pub fn translate(self, offset: Vec3) -> Sdf {
+        Sdf::Translate(offset, Box::new(self))
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, k: f32) -> Sdf {
+        Sdf::SmoothUnion(Box::new(self), Box::new(other), k)
+    }
+
+    pub fn subtract(self, other: Sdf) -> Sdf {
+        Sdf::Subtract(Box::new(self), Box::new(other))
+    }
+
+    pub fn repeat(self, period: Vec3, count: [i32; 3]) -> Sdf {
+        Sdf::Repeat(period, count, Box::new(self))
+    }
+
+    pub fn twist(self, radians_per_unit: f32) -> Sdf {
+        Sdf::Twist(radians_per_unit, Box::new(self))
+    }
+
+    pub fn distance(&self, p: Point3) -> f32 {
+        match self {
+            Sdf::Sphere(radius) => p.length() - radius,
+            Sdf::RoundBox(half_extents, radius) => {
+                let q = Vec3::new(
+                    p.x().abs() - half_extents.x() + radius,
+                    p.y().abs() - half_extents.y() + radius,
+                    p.z().abs() - half_extents.z() + radius,
+                );
+                let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+                outside.length() + q.x().max(q.y().max(q.z())).min(0.0) - radius
+            }
+            Sdf::Torus(major_radius, minor_radius) => {
+                let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - major_radius;
+                (ring * ring + p.y() * p.y()).sqrt() - minor_radius
+            }
+            Sdf::Translate(offset, inner) => inner.distance(p - *offset),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::SmoothUnion(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                d2 + h * (d1 - d2) - k * h * (1.0 - h)
+            }
+            Sdf::Subtract(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::Repeat(period, count, inner) => {
+                let mut q = p;
+                for axis in 0..3 {
+                    if period[axis] > 0.0 {
+                        let cell = (p[axis] / period[axis]).round().clamp(-count[axis] as f32, count[axis] as f32);
+                        q[axis] = p[axis] - period[axis] * cell;
+                    }
+                }
+                inner.distance(q)
+            }
+            Sdf::Twist(k, inner) => {
+                let (s, c) = (k * p.y()).sin_cos();
+                inner.distance(Vec3::new(c * p.x() - s * p.z(), p.y(), s * p.x() + c * p.z()))
+            }
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            Sdf::Sphere(radius) => {
+                let r = Vec3::new(*radius, *radius, *radius);
+                Aabb::new(-1.0 * r, r)
+            }
+            Sdf::RoundBox(half_extents, _) => Aabb::new(-1.0 * *half_extents, *half_extents),
+            Sdf::Torus(major_radius, minor_radius) => {
+                let e = Vec3::new(major_radius + minor_radius, *minor_radius, major_radius + minor_radius);
+                Aabb::new(-1.0 * e, e)
+            }
+            Sdf::Translate(offset, inner) => {
+                let b = inner.bounds();
+                Aabb::new(b.min() + *offset, b.max() + *offset)
+            }
+            Sdf::Union(a, b) => Aabb::surrounding_box(a.bounds(), b.bounds()),
+            Sdf::SmoothUnion(a, b, k) => {
+                let b = Aabb::surrounding_box(a.bounds(), b.bounds());
+                let pad = Vec3::new(*k, *k, *k);
+                Aabb::new(b.min() - pad, b.max() + pad)
+            }
+            Sdf::Subtract(a, _) => a.bounds(),
+            Sdf::Repeat(period, count, inner) => {
+                let b = inner.bounds();
+                let reach = Vec3::new(
+                    period.x() * count[0] as f32,
+                    period.y() * count[1] as f32,
+                    period.z() * count[2] as f32,
+                );
+                Aabb::new(b.min() - reach, b.max() + reach)
+            }
+            Sdf::Twist(_, inner) => {
+                // Whatever the rotation it stays inside the cylinder around Y
+                let b = inner.bounds();
+                let radius = Vec3::new(
+                    b.min().x().abs().max(b.max().x().abs()),
+                    0.0,
+                    b.min().z().abs().max(b.max().z().abs()),
+                )
+                .length();
+                Aabb::new(
+                    Point3::new(-radius, b.min().y(), -radius),
+                    Point3::new(radius, b.max().y(), radius),
+                )
+            }
+        }
+    }
+
+    // Twisting stretches distances, so steps must be shortened to avoid overshooting
+    fn step_scale(&self) -> f32 {
+        match self {
+            Sdf::Sphere(_) | Sdf::RoundBox(..) | Sdf::Torus(..) => 1.0,
+            Sdf::Translate(_, inner) | Sdf::Repeat(_, _, inner) => inner.step_scale(),
+            Sdf::Union(a, b) | Sdf::SmoothUnion(a, b, _) | Sdf::Subtract(a, b) => {
+                a.step_scale().min(b.step_scale())
+            }
+            Sdf::Twist(k, inner) => {
+                let b = inner.bounds();
+                let radius = b.max().length().max(b.min().length());
+                inner.step_scale() / (1.0 + (k * radius).powi(2)).sqrt()
+            }
+        }
+    }
+}
+
+pub struct SdfObject {
+    sdf: Sdf,
+    bounds: Aabb,
+    step_scale: f32,
+    mat: Arc<dyn Scatter>,
+}
+
+impl SdfObject {
+    const MAX_STEPS: u32 = 512;
+    const EPSILON: f32 = 1e-4;
+
+    pub fn new(sdf: Sdf, m: Arc<dyn Scatter>) -> SdfObject {
+        let bounds = sdf.bounds();
+        let pad = Vec3::new(10.0 * Self::EPSILON, 10.0 * Self::EPSILON, 10.0 * Self::EPSILON);
+        SdfObject {
+            step_scale: sdf.step_scale(),
+            bounds: Aabb::new(bounds.min() - pad, bounds.max() + pad),
+            sdf,
+            mat: m,
+        }
+    }
+
+    // Gradient by central differences on a tetrahedron
+    fn normal(&self, p: Point3) -> Vec3 {
+        let h = Self::EPSILON;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        k.iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |n, &k| n + self.sdf.distance(p + h * k) * k)
+            .unit_vector()
+    }
+}
+
+impl Hit for SdfObject {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (mut t, t_end) = self.bounds.clip(r, t_min, t_max)?;
+        let ray_length = r.direction().length();
+
+        // Rays starting inside march towards the exit following the negative
+        // distance. Rays leaving the surface start on it, their side is the one
+        // they head to and they have to get away before a hit counts.
+        let start = self.sdf.distance(r.at(t));
+        let mut leaving = start.abs() < Self::EPSILON;
+        let side = if leaving {
+            self.normal(r.at(t)).dot(r.direction()).signum()
+        } else {
+            start.signum()
+        };
+        for _ in 0..Self::MAX_STEPS {
+            let d = side * self.sdf.distance(r.at(t));
+            if leaving {
+                leaving = d < Self::EPSILON;
+            } else if d < Self::EPSILON {
+                let outward_normal = self.normal(r.at(t));
+                return Some(HitRecord::new(r, t, outward_normal, self.mat.clone()));
+            }
+
+            t += self.step_scale * d.max(Self::EPSILON) / ray_length;
+            if t > t_end {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
@@ -0,0 +1,121 @@
+use super::vec::{Color, Vec3};
+use std::sync::OnceLock;
+
+// Visible range sampled for wavelength dependent effects, in nanometers
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+pub fn sample_wavelength(u: f32) -> f32 {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+// CIE 1931 color matching functions, multi lobe Gaussian fit by Wyman, Sloan
+// and Shirley (2013)
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+    let g = |mu: f32, sigma1: f32, sigma2: f32| {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// To linear sRGB with its D65 white
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x() - 1.5372 * xyz.y() - 0.4986 * xyz.z(),
+        -0.9689 * xyz.x() + 1.8758 * xyz.y() + 0.0415 * xyz.z(),
+        0.0557 * xyz.x() - 0.2040 * xyz.y() + 1.0570 * xyz.z(),
+    )
+}
+
+// Color of a single wavelength, without the out of gamut negative parts
+fn wavelength_rgb(lambda: f32) -> Color {
+    let c = xyz_to_rgb(cie_xyz(lambda));
+    Color::new(c.r().max(0.0), c.g().max(0.0), c.b().max(0.0))
+}
+
+// Weight turning a path carrying one uniformly sampled wavelength into RGB,
+// scaled so that all wavelengths together average to white
+pub fn wavelength_to_rgb(lambda: f32) -> Color {
+    static AVERAGE: OnceLock<Color> = OnceLock::new();
+    let average = AVERAGE.get_or_init(|| {
+        const STEPS: usize = 1000;
+        let sum = (0..STEPS).fold(Color::new(0.0, 0.0, 0.0), |sum, i| {
+            sum + wavelength_rgb(sample_wavelength((i as f32 + 0.5) / STEPS as f32))
+        });
+        sum / STEPS as f32
+    });
+    wavelength_rgb(lambda) / *average
+}
+
+// Average of the matching functions over the sampled range, the XYZ of a
+// flat spectrum of one
+fn flat_xyz() -> Vec3 {
+    static FLAT: OnceLock<Vec3> = OnceLock::new();
+    *FLAT.get_or_init(|| {
+        const STEPS: usize = 1000;
+        let sum = (0..STEPS).fold(Vec3::new(0.0, 0.0, 0.0), |sum, i| {
+            sum + cie_xyz(sample_wavelength((i as f32 + 0.5) / STEPS as f32))
+        });
+        sum / STEPS as f32
+    })
+}
+
+// XYZ of radiance one at a uniformly sampled wavelength, scaled so that a flat
+// spectrum averages to a luminance of one
+pub fn wavelength_to_xyz(lambda: f32) -> Vec3 {
+    cie_xyz(lambda) / flat_xyz().y()
+}
+
+// Averaged XYZ to linear sRGB, white balanced so a flat spectrum is white
+pub fn xyz_to_balanced_rgb(xyz: Vec3) -> Color {
+    let white = xyz_to_rgb(flat_xyz() / flat_xyz().y());
+    let c = xyz_to_rgb(xyz) / white;
+    Color::new(c.r().max(0.0), c.g().max(0.0), c.b().max(0.0))
+}
+
+// Smits (1999) spectra for RGB upsampling, ten bins evenly covering the range
+const SMITS_WHITE: [f32; 10] = [1.0, 1.0, 0.9999, 0.9993, 0.9992, 0.9998, 1.0, 1.0, 1.0, 1.0];
+const SMITS_CYAN: [f32; 10] = [0.971, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0];
+const SMITS_MAGENTA: [f32; 10] = [1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959];
+const SMITS_YELLOW: [f32; 10] = [0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.984];
+const SMITS_RED: [f32; 10] = [0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f32; 10] = [0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025];
+const SMITS_BLUE: [f32; 10] = [1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Value at lambda of a smooth spectrum with the given RGB color, after Smits
+pub fn rgb_to_spectrum(c: Color, lambda: f32) -> f32 {
+    // Linear between the bin centers
+    let x = ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 10.0 - 0.5).clamp(0.0, 9.0);
+    let i = (x.floor() as usize).min(8);
+    let f = x - i as f32;
+    let at = |bins: &[f32; 10]| (1.0 - f) * bins[i] + f * bins[i + 1];
+
+    let (r, g, b) = (c.r(), c.g(), c.b());
+    if r <= g && r <= b {
+        let rest = if g <= b {
+            (g - r) * at(&SMITS_CYAN) + (b - g) * at(&SMITS_BLUE)
+        } else {
+            (b - r) * at(&SMITS_CYAN) + (g - b) * at(&SMITS_GREEN)
+        };
+        r * at(&SMITS_WHITE) + rest
+    } else if g <= r && g <= b {
+        let rest = if r <= b {
+            (r - g) * at(&SMITS_MAGENTA) + (b - r) * at(&SMITS_BLUE)
+        } else {
+            (b - g) * at(&SMITS_MAGENTA) + (r - b) * at(&SMITS_RED)
+        };
+        g * at(&SMITS_WHITE) + rest
+    } else {
+        let rest = if r <= g {
+            (r - b) * at(&SMITS_YELLOW) + (g - r) * at(&SMITS_GREEN)
+        } else {
+            (g - b) * at(&SMITS_YELLOW) + (r - g) * at(&SMITS_RED)
+        };
+        b * at(&SMITS_WHITE) + rest
+    }
+}
@@ -1,9 +1,53 @@
-use super::hit::{Hit, HitRecord};
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, Span};
 use super::mat::Scatter;
 use super::ray::Ray;
 use super::vec::{Point3, Vec3};
+use std::f32::consts::PI;
 use std::sync::Arc;
 
+// p is a point on the unit sphere, u goes around the Y axis from X = -1 and v from Y = -1 to Y = +1
+pub fn get_sphere_uv(p: Point3) -> (f32, f32) {
+    let theta = (-p.y()).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+
+    (phi / (2.0 * PI), theta / PI)
+}
+
+fn sphere_record(r: &Ray, root: f32, center: Point3, radius: f32, mat: &Arc<dyn Scatter>) -> HitRecord {
+    let outward_normal = (r.at(root) - center) / radius;
+    let mut rec = HitRecord::new(r, root, outward_normal, mat.clone());
+    (rec.u, rec.v) = get_sphere_uv(outward_normal);
+
+    // Derivatives of the mapping above, dpdv vanishes at the poles
+    let n = outward_normal;
+    let rho = (n.x() * n.x() + n.z() * n.z()).sqrt();
+    rec.dpdu = (2.0 * PI * radius) * Vec3::new(n.z(), 0.0, -n.x());
+    if rho > 0.0 {
+        rec.dpdv = (PI * radius) * Vec3::new(-n.y() * n.x() / rho, rho, -n.y() * n.z() / rho);
+    }
+    rec
+}
+
+fn sphere_span(r: &Ray, center: Point3, radius: f32, mat: &Arc<dyn Scatter>) -> Vec<Span> {
+    let oc = r.origin() - center;
+    let a = r.direction().length_squared();
+    let half_b = oc.dot(r.direction());
+    let c = oc.length_squared() - radius.powi(2);
+
+    let discriminant = half_b.powi(2) - a * c;
+    if discriminant <= 0.0 {
+        return Vec::new();
+    }
+
+    let sqrtd = discriminant.sqrt();
+
+    vec![Span {
+        enter: sphere_record(r, (-half_b - sqrtd) / a, center, radius, mat),
+        exit: sphere_record(r, (-half_b + sqrtd) / a, center, radius, mat),
+    }]
+}
+
 pub struct Sphere {
     center: Point3,
     radius: f32,
@@ -43,18 +87,16 @@ impl Hit for Sphere {
             }
         }
 
-        let mut rec = HitRecord {
-            t: root,
-            p: r.at(root),
-            mat: self.mat.clone(),
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            front_face: false,
-        };
+        Some(sphere_record(r, root, self.center, self.radius, &self.mat))
+    }
 
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let radius = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - radius, self.center + radius))
+    }
 
-        Some(rec)
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        sphere_span(r, self.center, self.radius, &self.mat)
     }
 }
 
@@ -113,17 +155,17 @@ impl Hit for MovingSphere {
             }
         }
 
-        let mut rec = HitRecord {
-            t: root,
-            p: r.at(root),
-            mat: self.mat.clone(),
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            front_face: false,
-        };
+        Some(sphere_record(r, root, self.center(r.time()), self.radius, &self.mat))
+    }
 
-        let outward_normal = (rec.p - self.center(r.time())) / self.radius;
-        rec.set_face_normal(r, outward_normal);
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let radius = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = Aabb::new(self.center(time0) - radius, self.center(time0) + radius);
+        let box1 = Aabb::new(self.center(time1) - radius, self.center(time1) + radius);
+        Some(Aabb::surrounding_box(box0, box1))
+    }
 
-        Some(rec)
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        sphere_span(r, self.center(r.time()), self.radius, &self.mat)
     }
 }
@@ -0,0 +1,65 @@
+use super::mesh::Mesh;
+use super::vec::Point3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// ASCII or binary STL. Facets don't share vertices so the mesh is flat shaded.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Mesh> {
+    let data = fs::read(path)?;
+
+    // Binary files may also start with "solid", the size tells them apart
+    let binary_count = data
+        .get(80..84)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let positions = match binary_count {
+        Some(count) if data.len() == 84 + 50 * count => load_binary(&data[84..], count),
+        _ if data.starts_with(b"solid") => load_ascii(&String::from_utf8_lossy(&data))?,
+        _ => return Err(invalid_data("not an stl file")),
+    };
+
+    let triangles = (0..positions.len() / 3).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+    Ok(Mesh {
+        positions,
+        triangles,
+        ..Mesh::default()
+    })
+}
+
+// Each facet is a normal, three vertices and a two byte attribute
+fn load_binary(data: &[u8], count: usize) -> Vec<Point3> {
+    let float = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let mut positions = Vec::with_capacity(3 * count);
+    for facet in data.chunks_exact(50).take(count) {
+        for vertex in facet[12..48].chunks_exact(12) {
+            positions.push(Point3::new(float(&vertex[0..4]), float(&vertex[4..8]), float(&vertex[8..12])));
+        }
+    }
+    positions
+}
+
+fn load_ascii(text: &str) -> io::Result<Vec<Point3>> {
+    let mut positions = Vec::new();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        if word != "vertex" {
+            continue;
+        }
+        let mut coordinate = || -> io::Result<f32> {
+            words
+                .next()
+                .and_then(|w| w.parse::<f32>().ok())
+                .ok_or_else(|| invalid_data("bad vertex in stl file"))
+        };
+        positions.push(Point3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+
+    if positions.len() % 3 != 0 {
+        return Err(invalid_data("stl facet without three vertices"));
+    }
+    Ok(positions)
+}
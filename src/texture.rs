@@ -0,0 +1,64 @@
+use super::image::Image;
+use super::vec::{Color, Point3};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color;
+}
+
+pub struct SolidColor {
+    color_value: Color,
+}
+
+impl SolidColor {
+    pub fn new(c: Color) -> SolidColor {
+        SolidColor { color_value: c }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: Point3) -> Color {
+        self.color_value
+    }
+}
+
+// Gradient through evenly spaced colors following u
+pub struct ColorRamp {
+    colors: Vec<Color>,
+}
+
+impl ColorRamp {
+    pub fn new(colors: Vec<Color>) -> ColorRamp {
+        assert!(!colors.is_empty(), "color ramp needs at least one color");
+        ColorRamp { colors }
+    }
+}
+
+impl Texture for ColorRamp {
+    fn value(&self, u: f32, _v: f32, _p: Point3) -> Color {
+        let x = u.clamp(0.0, 1.0) * (self.colors.len() - 1) as f32;
+        let i = (x.floor() as usize).min(self.colors.len() - 1);
+        let j = (i + 1).min(self.colors.len() - 1);
+        let f = x - i as f32;
+
+        (1.0 - f) * self.colors[i] + f * self.colors[j]
+    }
+}
+
+// Image repeated over uv, with v going up from the bottom row
+pub struct ImageTexture {
+    image: Image,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> ImageTexture {
+        ImageTexture { image }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: Point3) -> Color {
+        let x = ((u - u.floor()) * self.image.width() as f32) as usize;
+        let y = ((1.0 - (v - v.floor())) * self.image.height() as f32) as usize;
+        self.image.pixel(x, y)
+    }
+}
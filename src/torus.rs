@@ -0,0 +1,196 @@
+use super::aabb::Aabb;
+use super::cylinder::{azimuth, azimuth_derivative, radial};
+use super::hit::{Hit, HitRecord, Span};
+use super::mat::Scatter;
+use super::onb::Onb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+use std::sync::Arc;
+
+// Largest real root of x^3 + a x^2 + b x + c = 0
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // Depressed cubic z^3 + p z + q = 0 with x = z - a/3
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    let z = if discriminant >= 0.0 {
+        let sqrtd = discriminant.sqrt();
+        (-q / 2.0 + sqrtd).cbrt() + (-q / 2.0 - sqrtd).cbrt()
+    } else {
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        m * theta.cos()
+    };
+
+    z - a / 3.0
+}
+
+fn quadratic_roots(b: f64, c: f64, roots: &mut Vec<f64>) {
+    let discriminant = b * b - 4.0 * c;
+    if discriminant >= 0.0 {
+        let sqrtd = discriminant.sqrt();
+        roots.push((-b - sqrtd) / 2.0);
+        roots.push((-b + sqrtd) / 2.0);
+    }
+}
+
+// Real roots of c4 t^4 + c3 t^3 + c2 t^2 + c1 t + c0 = 0, Ferrari's method
+fn solve_quartic(c4: f64, c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+
+    // Depressed quartic y^4 + p y^2 + q y + r = 0 with t = y - a/4
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    if q.abs() < 1e-12 {
+        // Biquadratic, solve for y^2
+        let mut squares = Vec::with_capacity(2);
+        quadratic_roots(p, r, &mut squares);
+        for y2 in squares.into_iter().filter(|&y2| y2 >= 0.0) {
+            roots.push(-y2.sqrt());
+            roots.push(y2.sqrt());
+        }
+    } else {
+        // Resolvent cubic, its largest root is positive whenever q != 0
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 {
+            return Vec::new();
+        }
+        let s = (2.0 * m).sqrt();
+        quadratic_roots(-s, p / 2.0 + m + q / (2.0 * s), &mut roots);
+        quadratic_roots(s, p / 2.0 + m - q / (2.0 * s), &mut roots);
+    }
+
+    // Undo the shift and polish with a couple of Newton steps
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut t = y - a / 4.0;
+            for _ in 0..2 {
+                let f = (((c4 * t + c3) * t + c2) * t + c1) * t + c0;
+                let df = ((4.0 * c4 * t + 3.0 * c3) * t + 2.0 * c2) * t + c1;
+                if df != 0.0 {
+                    t -= f / df;
+                }
+            }
+            t
+        })
+        .collect()
+}
+
+// Ring around the axis through center, major_radius to the middle of the tube
+pub struct Torus {
+    center: Point3,
+    major_radius: f32,
+    minor_radius: f32,
+    onb: Onb,
+    local_bounds: Aabb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Torus {
+    pub fn new(center: Point3, axis: Vec3, major_radius: f32, minor_radius: f32, m: Arc<dyn Scatter>) -> Torus {
+        let extent = Vec3::new(major_radius + minor_radius, major_radius + minor_radius, minor_radius);
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            onb: Onb::build_from_w(axis),
+            local_bounds: Aabb::new(-1.0 * extent, extent),
+            mat: m,
+        }
+    }
+
+    // Every crossing of the whole line of r with the surface, sorted by distance
+    fn crossings(&self, r: &Ray) -> Vec<HitRecord> {
+        let local = Ray::new(
+            self.onb.world_to_local(r.origin() - self.center),
+            self.onb.world_to_local(r.direction()),
+            r.time(),
+        );
+
+        // Solving from where the ray enters the bounds with a unit direction
+        // keeps the quartic well conditioned for far away origins.
+        let (t_enter, _) = match self.local_bounds.clip(&local, f32::NEG_INFINITY, f32::INFINITY) {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        let length = local.direction().length() as f64;
+        let o = local.at(t_enter);
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let d = local.direction();
+        let (dx, dy, dz) = (d.x() as f64 / length, d.y() as f64 / length, d.z() as f64 / length);
+
+        let big_r2 = (self.major_radius as f64).powi(2);
+        let small_r2 = (self.minor_radius as f64).powi(2);
+
+        // (|o + s d|^2 + R^2 - r^2)^2 = 4 R^2 ((ox + s dx)^2 + (oy + s dy)^2), with |d| = 1
+        let beta = 2.0 * (ox * dx + oy * dy + oz * dz);
+        let gamma = ox * ox + oy * oy + oz * oz + big_r2 - small_r2;
+        let a2 = dx * dx + dy * dy;
+        let a1 = 2.0 * (ox * dx + oy * dy);
+        let a0 = ox * ox + oy * oy;
+
+        let c4 = 1.0;
+        let c3 = 2.0 * beta;
+        let c2 = beta * beta + 2.0 * gamma - 4.0 * big_r2 * a2;
+        let c1 = 2.0 * beta * gamma - 4.0 * big_r2 * a1;
+        let c0 = gamma * gamma - 4.0 * big_r2 * a0;
+
+        let mut crossings: Vec<HitRecord> = solve_quartic(c4, c3, c2, c1, c0)
+            .into_iter()
+            .map(|s| {
+                let root = t_enter + (s / length) as f32;
+                let p = local.at(root);
+
+                let sum = p.length_squared() + self.major_radius.powi(2) - self.minor_radius.powi(2);
+                let outward_normal = Vec3::new(
+                    p.x() * (sum - 2.0 * self.major_radius.powi(2)),
+                    p.y() * (sum - 2.0 * self.major_radius.powi(2)),
+                    p.z() * sum,
+                )
+                .unit_vector();
+
+                let mut rec = HitRecord::new(r, root, self.onb.local(outward_normal), self.mat.clone());
+                rec.u = azimuth(p);
+                // Angle around the tube, starting on the outer equator
+                let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                let tube = Vec3::new(rho - self.major_radius, p.z(), 0.0);
+                rec.v = azimuth(tube);
+                rec.dpdu = self.onb.local(azimuth_derivative(p));
+                let around_tube = azimuth_derivative(tube);
+                rec.dpdv = self.onb.local(around_tube.x() * radial(p) + Vec3::new(0.0, 0.0, around_tube.y()));
+                rec
+            })
+            .collect();
+
+        crossings.sort_by(|x, y| x.t.total_cmp(&y.t));
+        crossings
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.crossings(r)
+            .into_iter()
+            .find(|rec| t_min <= rec.t && rec.t <= t_max)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let w = self.onb.w();
+        let extent = Vec3::new(
+            self.major_radius * (1.0 - w.x() * w.x()).max(0.0).sqrt() + self.minor_radius,
+            self.major_radius * (1.0 - w.y() * w.y()).max(0.0).sqrt() + self.minor_radius,
+            self.major_radius * (1.0 - w.z() * w.z()).max(0.0).sqrt() + self.minor_radius,
+        );
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        Span::from_crossings(self.crossings(r))
+    }
+}
@@ -0,0 +1,160 @@
+use super::vec::{Point3, Vec3};
+
+// Affine transform as a row major 4x4 matrix, applied to column vectors
+#[derive(Clone, Copy)]
+pub struct Transform {
+    m: [[f32; 4]; 4],
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Transform { m }
+    }
+
+    // Column major matrix, as glTF and pbrt store them
+    pub fn from_columns(c: [[f32; 4]; 4]) -> Transform {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = c[j][i];
+            }
+        }
+        Transform { m }
+    }
+
+    pub fn translate(d: Vec3) -> Transform {
+        let mut t = Transform::identity();
+        for a in 0..3 {
+            t.m[a][3] = d[a];
+        }
+        t
+    }
+
+    pub fn scale(s: Vec3) -> Transform {
+        let mut t = Transform::identity();
+        for a in 0..3 {
+            t.m[a][a] = s[a];
+        }
+        t
+    }
+
+    // Counterclockwise around axis when looking down it
+    pub fn rotate(degrees: f32, axis: Vec3) -> Transform {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut t = Transform::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                // Cross product matrix of the axis
+                let cross = match (i, j) {
+                    (0, 1) => -a.z(),
+                    (1, 0) => a.z(),
+                    (0, 2) => a.y(),
+                    (2, 0) => -a.y(),
+                    (1, 2) => -a.x(),
+                    (2, 1) => a.x(),
+                    _ => 0.0,
+                };
+                let identity = if i == j { 1.0 } else { 0.0 };
+                t.m[i][j] = a[i] * a[j] * (1.0 - cos) + identity * cos + cross * sin;
+            }
+        }
+        t
+    }
+
+    // World to camera for a camera at eye looking at look, as pbrt builds it.
+    // Camera space has x right, y up and z forward.
+    pub fn look_at(eye: Point3, look: Point3, up: Vec3) -> Transform {
+        let dir = (look - eye).unit_vector();
+        let right = up.unit_vector().cross(dir).unit_vector();
+        let new_up = dir.cross(right);
+        let camera_to_world = Transform::from_columns([
+            [right.x(), right.y(), right.z(), 0.0],
+            [new_up.x(), new_up.y(), new_up.z(), 0.0],
+            [dir.x(), dir.y(), dir.z(), 0.0],
+            [eye.x(), eye.y(), eye.z(), 1.0],
+        ]);
+        camera_to_world.inverse()
+    }
+
+    // Gauss-Jordan with partial pivoting, singular matrices give the identity
+    pub fn inverse(&self) -> Transform {
+        let mut a = self.m;
+        let mut inv = Transform::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col] == 0.0 {
+                return Transform::identity();
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for i in 0..4 {
+                if i != col {
+                    let f = a[i][col];
+                    for j in 0..4 {
+                        a[i][j] -= f * a[col][j];
+                        inv[i][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Transform { m: inv }
+    }
+
+    // Applies other first and then self
+    pub fn compose(&self, other: &Transform) -> Transform {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Transform { m }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+        let w = row(3);
+        Point3::new(row(0), row(1), row(2)) / if w != 0.0 { w } else { 1.0 }
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // Normals go through the inverse transpose, the cofactor matrix is the
+    // same up to a scale which the unit_vector takes away
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let a = |i: usize, j: usize| self.m[i][j];
+        let cofactor = |i: usize, j: usize| {
+            let (i1, i2) = ((i + 1) % 3, (i + 2) % 3);
+            let (j1, j2) = ((j + 1) % 3, (j + 2) % 3);
+            a(i1, j1) * a(i2, j2) - a(i1, j2) * a(i2, j1)
+        };
+        let row = |i: usize| cofactor(i, 0) * n.x() + cofactor(i, 1) * n.y() + cofactor(i, 2) * n.z();
+        let sign = if self.determinant() < 0.0 { -1.0 } else { 1.0 };
+        (sign * Vec3::new(row(0), row(1), row(2))).unit_vector()
+    }
+
+    // Of the upper 3x3, negative when the transform mirrors
+    pub fn determinant(&self) -> f32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
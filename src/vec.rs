@@ -15,7 +15,6 @@ pub type Color = Vec3;
 pub type Point3 = Vec3;
 
 #[allow(dead_code)]
-
 impl Vec3 {
     pub fn new(e0: f32, e1: f32, e2: f32) -> Self {
         Vec3 { e: [e0, e1, e2] }
@@ -0,0 +1,266 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::mat::Scatter;
+use super::ray::Ray;
+use super::vec::{Color, Point3, Vec3};
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+/*
+Raw grid file, everything little endian:
+    u32 nx, u32 ny, u32 nz, u32 channels (1 to 3)
+    nx * ny * nz * channels f32 values, x varies fastest, then y, then z.
+Channels in order: density, temperature (Kelvin), emission.
+*/
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    density: Vec<f32>,
+    temperature: Option<Vec<f32>>,
+    emission: Option<Vec<f32>>,
+    max_density: f32,
+}
+
+impl VoxelGrid {
+    pub fn new(
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        density: Vec<f32>,
+        temperature: Option<Vec<f32>>,
+        emission: Option<Vec<f32>>,
+    ) -> VoxelGrid {
+        let count = nx * ny * nz;
+        assert_eq!(density.len(), count, "density channel does not match grid size");
+        assert!(temperature.as_ref().is_none_or(|c| c.len() == count));
+        assert!(emission.as_ref().is_none_or(|c| c.len() == count));
+
+        let max_density = density.iter().cloned().fold(0.0, f32::max);
+        VoxelGrid {
+            nx,
+            ny,
+            nz,
+            density,
+            temperature,
+            emission,
+            max_density,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<VoxelGrid> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u32; 4];
+        for value in header.iter_mut() {
+            let mut bytes = [0u8; 4];
+            reader.read_exact(&mut bytes)?;
+            *value = u32::from_le_bytes(bytes);
+        }
+        let [nx, ny, nz, channels] = header.map(|v| v as usize);
+        if !(1..=3).contains(&channels) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("voxel grid must have 1 to 3 channels, found {}", channels),
+            ));
+        }
+
+        if nx == 0 || ny == 0 || nz == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "voxel grid has an empty dimension"));
+        }
+        let too_big = || io::Error::new(io::ErrorKind::InvalidData, "voxel grid is too big");
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz)).ok_or_else(too_big)?;
+        let size = count.checked_mul(channels * 4).ok_or_else(too_big)?;
+        // Read before allocating, a corrupt header can't ask for all the memory
+        let mut data = Vec::new();
+        reader.take(size as u64).read_to_end(&mut data)?;
+        if data.len() != size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "voxel grid file is truncated"));
+        }
+        let values: Vec<f32> = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        let mut channel_data = values.chunks_exact(count).map(|c| c.to_vec());
+        let density = channel_data.next().unwrap();
+        let temperature = channel_data.next();
+        let emission = channel_data.next();
+
+        Ok(VoxelGrid::new(nx, ny, nz, density, temperature, emission))
+    }
+
+    // Trilinear lookup with voxel values at the cell centers, p in [0,1]^3
+    fn lookup(&self, channel: &[f32], p: Vec3) -> f32 {
+        let dims = [self.nx, self.ny, self.nz];
+        let mut i0 = [0usize; 3];
+        let mut i1 = [0usize; 3];
+        let mut f = [0.0f32; 3];
+        for a in 0..3 {
+            let g = (p[a] * dims[a] as f32 - 0.5).clamp(0.0, (dims[a] - 1) as f32);
+            i0[a] = g.floor() as usize;
+            i1[a] = (i0[a] + 1).min(dims[a] - 1);
+            f[a] = g - i0[a] as f32;
+        }
+
+        let at = |x: usize, y: usize, z: usize| channel[(z * self.ny + y) * self.nx + x];
+        let lerp = |a: f32, b: f32, t: f32| a + t * (b - a);
+
+        let c00 = lerp(at(i0[0], i0[1], i0[2]), at(i1[0], i0[1], i0[2]), f[0]);
+        let c10 = lerp(at(i0[0], i1[1], i0[2]), at(i1[0], i1[1], i0[2]), f[0]);
+        let c01 = lerp(at(i0[0], i0[1], i1[2]), at(i1[0], i0[1], i1[2]), f[0]);
+        let c11 = lerp(at(i0[0], i1[1], i1[2]), at(i1[0], i1[1], i1[2]), f[0]);
+
+        lerp(lerp(c00, c10, f[1]), lerp(c01, c11, f[1]), f[2])
+    }
+
+    pub fn density(&self, p: Vec3) -> f32 {
+        self.lookup(&self.density, p)
+    }
+
+    pub fn temperature(&self, p: Vec3) -> f32 {
+        self.temperature.as_ref().map_or(0.0, |c| self.lookup(c, p))
+    }
+
+    pub fn emission(&self, p: Vec3) -> f32 {
+        self.emission.as_ref().map_or(0.0, |c| self.lookup(c, p))
+    }
+}
+
+// Approximate color of a black body, normalized so the brightest channel is 1
+pub fn blackbody(kelvin: f32) -> Color {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let g = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_16 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    Color::new(
+        r.clamp(0.0, 255.0) / 255.0,
+        g.clamp(0.0, 255.0) / 255.0,
+        b.clamp(0.0, 255.0) / 255.0,
+    )
+}
+
+struct GridPhase {
+    grid: Arc<VoxelGrid>,
+    min: Point3,
+    size: Vec3,
+    albedo: Color,
+    emission_color: Color,
+    temperature_scale: f32,
+}
+
+impl GridPhase {
+    fn local(&self, p: Point3) -> Vec3 {
+        (p - self.min) / self.size
+    }
+}
+
+impl Scatter for GridPhase {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let scattered = Ray::new(rec.p, Vec3::random_in_unit_sphere().unit_vector(), r_in.time());
+
+        Some((self.albedo, scattered))
+    }
+
+    // Only the absorbed fraction of a collision emits, the rest is carried by the scattered ray
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let p = self.local(rec.p);
+        let mut le = self.grid.emission(p) * self.emission_color;
+
+        let kelvin = self.grid.temperature(p);
+        if kelvin > 0.0 {
+            le += self.temperature_scale * (kelvin / 1000.0).powi(4) * blackbody(kelvin);
+        }
+
+        (Color::new(1.0, 1.0, 1.0) - self.albedo) * le
+    }
+}
+
+pub struct GridMedium {
+    grid: Arc<VoxelGrid>,
+    bounds: Aabb,
+    density_scale: f32,
+    phase_function: Arc<GridPhase>,
+}
+
+impl GridMedium {
+    pub fn new(grid: VoxelGrid, p0: Point3, p1: Point3, density_scale: f32, albedo: Color) -> GridMedium {
+        let bounds = Aabb::new(p0, p1);
+        let grid = Arc::new(grid);
+
+        GridMedium {
+            grid: grid.clone(),
+            bounds,
+            density_scale,
+            phase_function: Arc::new(GridPhase {
+                grid,
+                min: bounds.min(),
+                size: bounds.max() - bounds.min(),
+                albedo,
+                emission_color: Color::new(1.0, 1.0, 1.0),
+                temperature_scale: 0.0,
+            }),
+        }
+    }
+
+    pub fn with_emission(mut self, emission_color: Color, temperature_scale: f32) -> GridMedium {
+        let phase = Arc::get_mut(&mut self.phase_function).expect("phase function already shared");
+        phase.emission_color = emission_color;
+        phase.temperature_scale = temperature_scale;
+        self
+    }
+
+    fn sigma_t(&self, p: Point3) -> f32 {
+        self.density_scale * self.grid.density(self.phase_function.local(p))
+    }
+}
+
+impl Hit for GridMedium {
+    // Delta tracking against the majorant of the whole grid
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let sigma_max = self.density_scale * self.grid.max_density;
+        if sigma_max <= 0.0 {
+            return None;
+        }
+        let (mut t, t_end) = self.bounds.clip(r, t_min.max(0.0), t_max)?;
+
+        let mut rng = rand::thread_rng();
+        let ray_length = r.direction().length();
+        loop {
+            t -= (1.0 - rng.gen::<f32>()).ln() / (sigma_max * ray_length);
+            if t >= t_end {
+                return None;
+            }
+
+            let p = r.at(t);
+            if rng.gen::<f32>() * sigma_max < self.sigma_t(p) {
+                // Like ConstantMedium, always front facing
+                let mut rec = HitRecord::new(r, t, Vec3::new(1.0, 0.0, 0.0), self.phase_function.clone());
+                rec.front_face = true;
+                return Some(rec);
+            }
+        }
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}